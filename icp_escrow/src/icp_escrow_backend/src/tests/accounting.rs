@@ -0,0 +1,122 @@
+use super::*;
+
+fn balance_on(balances: &[(Principal, Nat)], ledger: Principal) -> Nat {
+    balances.iter().find(|(l, _)| *l == ledger).map(|(_, b)| b.clone()).expect("ledger listed")
+}
+
+#[test]
+fn get_balances_counts_escrow_subaccounts_with_the_default_account() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    mock::mint(icp_ledger(), Account::from(mock::id()), 5);
+    let first = create(immutables(1, 1_000, 100)).unwrap();
+    let second = create(immutables(2, 2_000, 200)).unwrap();
+    deposit(&first, payer).unwrap();
+    deposit(&second, payer).unwrap();
+
+    assert_eq!(balance_on(&mock::run(get_balances()), icp_ledger()), 5u64 + 1_100 + 2_200);
+}
+
+#[test]
+fn total_locked_counts_funded_escrows_under_either_name_of_the_icp_ledger() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let funded = create(immutables(1, 1_000, 100)).unwrap();
+    create(immutables(2, 2_000, 200)).unwrap();
+    deposit(&funded, payer).unwrap();
+
+    assert_eq!(get_total_locked(None), 1_100u64);
+    assert_eq!(get_total_locked(Some(icp_ledger())), 1_100u64);
+    assert_eq!(get_total_locked(Some(principal(40))), 0u64);
+}
+
+#[test]
+fn reconcile_balances_when_subaccounts_hold_what_is_tracked() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+
+    let report = mock::run(reconcile(None)).unwrap();
+    assert_eq!(report.ledger, icp_ledger());
+    assert_eq!(report.tracked_locked, 1_100u64);
+    assert_eq!(report.actual_balance, 1_100u64);
+    assert!(report.balanced);
+}
+
+#[test]
+fn reconcile_reports_surplus_and_deficit() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    // Stray transfer into the default account
+    mock::mint(icp_ledger(), Account::from(mock::id()), 25);
+
+    let report = mock::run(reconcile(None)).unwrap();
+    assert_eq!(report.surplus, 25u64);
+    assert_eq!(report.deficit, 0u64);
+    assert!(!report.balanced);
+
+    // An escrow marked funded whose deposit never arrived
+    let phantom = create(immutables(2, 2_000, 200)).unwrap();
+    update_escrow(&phantom, |escrow| escrow.funded = true);
+
+    let report = mock::run(reconcile(Some(icp_ledger()))).unwrap();
+    assert_eq!(report.tracked_locked, 1_100u64 + 2_200);
+    assert_eq!(report.actual_balance, 1_100u64 + 25);
+    assert_eq!(report.deficit, 2_200u64 - 25);
+    assert!(!report.balanced);
+}
+
+#[test]
+fn reconcile_is_controller_only_and_surfaces_ledger_failures() {
+    mock::set_caller(principal(7));
+    assert_eq!(mock::run(reconcile(None)).unwrap_err(), EscrowError::Unauthorized);
+
+    mock::set_caller(mock::controller());
+    assert!(matches!(mock::run(reconcile(None)), Err(EscrowError::LedgerUnavailable(_))));
+}
+
+#[test]
+fn committed_amounts_sum_open_escrows_per_ledger() {
+    let ledger = principal(40);
+    let token = register_token(ledger);
+    create(immutables(1, 1_000, 100)).unwrap();
+    insert_escrow(
+        Immutables { token, ..immutables(2, 2_000, 200) },
+        principal(1), Some(ledger), Some(ledger), EVM_CHAIN, EVM_ESCROW.to_string(), None, None,
+    ).unwrap();
+    let withdrawn = create(immutables(3, 4_000, 400)).unwrap();
+    update_escrow(&withdrawn, |escrow| escrow.withdrawn = true);
+
+    let committed = get_committed_amounts();
+    assert_eq!(balance_on(&committed, icp_ledger()), 1_100u64);
+    assert_eq!(balance_on(&committed, ledger), 2_200u64);
+}
+
+#[test]
+fn get_balances_lists_every_known_ledger() {
+    with_icp_ledger(principal(7));
+    let ledger = principal(40);
+    register_token(ledger);
+    mock::mint(ledger, Account::from(mock::id()), 9);
+
+    let balances = mock::run(get_balances());
+    assert_eq!(balance_on(&balances, icp_ledger()), 0u64);
+    assert_eq!(balance_on(&balances, ledger), 9u64);
+}
+
+#[test]
+fn subaccount_check_flags_deposits_outside_the_derived_subaccount() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let placed = create(immutables(1, 1_000, 100)).unwrap();
+    let misplaced = create(immutables(2, 1_000, 100)).unwrap();
+    deposit(&placed, payer).unwrap();
+    // Funds sent to a subaccount an older derivation scheme would have used
+    mock::mint(icp_ledger(), Account { owner: mock::id(), subaccount: Some([2; 32]) }, 1_100);
+
+    let results = mock::run(verify_all_subaccounts()).unwrap();
+    assert_eq!(results, vec![(placed, true), (misplaced, false)]);
+}
@@ -0,0 +1,99 @@
+use super::*;
+
+/// Create an escrow under `key`, fund it and withdraw it
+fn completed_escrow(seed: u8, key: &str) -> String {
+    let payer = principal(7);
+    let escrow_id = mock::run(create_escrow_idempotent(
+        immutables(seed, 1_000, 100), principal(1), None, None, EVM_CHAIN, EVM_ESCROW.to_string(), None, None, key.to_string(),
+    )).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    mock::advance(100);
+    mock::run(withdraw_with_secret(escrow_id.clone(), secret(seed))).unwrap();
+    escrow_id
+}
+
+#[test]
+fn archived_escrows_stay_in_the_order_history() {
+    with_icp_ledger(principal(7));
+    let archived = completed_escrow(1, "first");
+    let open = create(immutables(1, 1_000, 100)).unwrap();
+
+    assert_eq!(archive_completed(0), Ok(1));
+    assert!(get_escrow_state(archived.clone()).is_none());
+    assert_eq!(get_archived_escrow(archived.clone()).unwrap().status, EscrowStatus::Withdrawn);
+
+    let history = get_order_history([1; 32]);
+    let statuses: Vec<_> = history.iter().map(|(id, status, _)| (id.clone(), *status)).collect();
+    assert_eq!(statuses, vec![(archived.clone(), EscrowStatus::Withdrawn), (open.clone(), EscrowStatus::Pending)]);
+
+    let records = get_escrows_by_order_hash([1; 32]);
+    assert!(matches!(&records[0], (id, EscrowRecord::Archived(_)) if *id == archived));
+    assert!(matches!(&records[1], (id, EscrowRecord::Full(_)) if *id == open));
+}
+
+#[test]
+fn archiving_drops_the_escrows_idempotency_keys() {
+    with_icp_ledger(principal(7));
+    completed_escrow(1, "first");
+    let kept = mock::run(create_escrow_idempotent(
+        immutables(2, 1_000, 100), principal(1), None, None, EVM_CHAIN, EVM_ESCROW.to_string(), None, None, "second".to_string(),
+    )).unwrap();
+
+    archive_completed(0).unwrap();
+
+    let keys: Vec<String> = IDEMPOTENCY_KEYS.with(|keys| keys.borrow().values().map(|c| c.escrow_id.clone()).collect());
+    assert_eq!(keys, vec![kept]);
+}
+
+#[test]
+fn only_escrows_completed_before_the_cutoff_are_archived() {
+    with_icp_ledger(principal(7));
+    let escrow_id = completed_escrow(1, "first");
+
+    assert_eq!(archive_completed(3_600), Ok(0));
+    mock::advance(3_600);
+    assert_eq!(archive_completed(3_600), Ok(1));
+    assert!(get_archived_escrow(escrow_id).is_some());
+}
+
+#[test]
+fn archive_drops_the_earliest_archived_escrows_beyond_its_bound() {
+    let entry = |archived_at: u64| ArchivedEscrow {
+        order_hash: [1; 32],
+        hashlock: [2; 32],
+        status: EscrowStatus::Withdrawn,
+        secret: None,
+        icp_recipient: principal(1),
+        token_ledger: None,
+        amount: 1_000,
+        evm_chain_id: EVM_CHAIN,
+        deployed_at: 0,
+        completed_at: archived_at,
+        archived_at,
+    };
+    let mut archive: HashMap<String, ArchivedEscrow> = [
+        ("escrow_3".to_string(), entry(10)),
+        ("escrow_1".to_string(), entry(20)),
+        ("escrow_2".to_string(), entry(10)),
+    ].into_iter().collect();
+
+    assert!(trim_archive(&mut archive, 3).is_empty());
+    let dropped: Vec<String> = trim_archive(&mut archive, 1).into_iter().map(|(id, _)| id).collect();
+    assert_eq!(dropped, vec!["escrow_2".to_string(), "escrow_3".to_string()]);
+    assert_eq!(archive.keys().collect::<Vec<_>>(), vec!["escrow_1"]);
+}
+
+#[test]
+fn audit_log_keeps_only_the_latest_entries() {
+    for _ in 0..MAX_AUDIT_LOG_ENTRIES + MAX_AUDIT_LOG_ENTRIES / 10 + 1 {
+        audit(AuditEvent::ConfigChanged, None, String::new());
+    }
+    let (len, first, last) = AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        (log.len(), log[0].seq, log[log.len() - 1].seq)
+    });
+    assert_eq!(len, MAX_AUDIT_LOG_ENTRIES);
+    // Sequence numbers keep counting across trims
+    assert_eq!(last as usize, MAX_AUDIT_LOG_ENTRIES + MAX_AUDIT_LOG_ENTRIES / 10 + 1);
+    assert_eq!(first, last - MAX_AUDIT_LOG_ENTRIES as u64 + 1);
+}
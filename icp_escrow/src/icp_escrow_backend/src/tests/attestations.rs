@@ -0,0 +1,52 @@
+use super::*;
+
+#[test]
+fn attestation_signs_the_eip712_digest_of_order_hash_and_secret() {
+    let (order_hash, revealed) = ([1u8; 32], secret(1));
+    let struct_hash = keccak256(&[
+        keccak256(b"RevealAttestation(bytes32 orderHash,bytes32 secret)"),
+        order_hash,
+        revealed,
+    ].concat());
+    assert_eq!(reveal_attestation_hash(&order_hash, &revealed), struct_hash);
+
+    let digest = reveal_attestation_digest(EVM_CHAIN, &order_hash, &revealed);
+    assert_eq!(digest, keccak256(&[&[0x19, 0x01][..], &eip712_domain_separator(EVM_CHAIN), &struct_hash].concat()));
+    // Bound to the chain and to the secret
+    assert_ne!(digest, reveal_attestation_digest(8453, &order_hash, &revealed));
+    assert_ne!(digest, reveal_attestation_digest(EVM_CHAIN, &order_hash, &secret(2)));
+}
+
+#[test]
+fn attestation_is_only_produced_once_the_secret_is_known() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    assert_eq!(mock::run(get_reveal_attestation(escrow_id.clone())), Ok(None));
+    assert_eq!(submit_secret(escrow_id.clone(), secret(2)), Err(EscrowError::InvalidSecret));
+    assert_eq!(mock::run(get_reveal_attestation(escrow_id.clone())), Ok(None));
+    assert_eq!(mock::ecdsa_calls(), 0);
+
+    submit_secret(escrow_id.clone(), secret(1)).unwrap();
+    let signature = mock::run(get_reveal_attestation(escrow_id)).unwrap().unwrap();
+    assert_eq!(signature.len(), 65);
+    let digest = reveal_attestation_digest(EVM_CHAIN, &[1u8; 32], &secret(1));
+    let signer = evm_address_of(mock::ecdsa_key().verifying_key());
+    assert_eq!(ecrecover(&digest, &signature), Ok(signer));
+    assert_eq!(mock::run(get_attestation_signer()), Ok(format!("0x{}", hex::encode(signer))));
+}
+
+#[test]
+fn repeated_attestation_requests_reuse_the_signature() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    submit_secret(escrow_id.clone(), secret(1)).unwrap();
+
+    let first = mock::run(get_reveal_attestation(escrow_id.clone())).unwrap();
+    let calls = mock::ecdsa_calls();
+    assert_eq!(mock::run(get_reveal_attestation(escrow_id.clone())).unwrap(), first);
+    mock::run(get_attestation_signer()).unwrap();
+    assert_eq!(mock::ecdsa_calls(), calls);
+
+    // A new key invalidates the cache
+    set_ecdsa_key_name("test_key_1".to_string()).unwrap();
+    assert_eq!(mock::run(get_reveal_attestation(escrow_id)).unwrap(), first);
+    assert!(mock::ecdsa_calls() > calls);
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+/// Auction starting 100s in (when Dst withdrawal opens) and decaying a 10% bump over 600s
+fn auction() -> AuctionParams {
+    AuctionParams { start_time: mock::GENESIS_SECS + 100, duration: 600, initial_rate_bump: 1_000_000 }
+}
+
+/// Native-ICP escrow of 1_000 (safety deposit 100) priced by `auction()`
+fn create_auction_escrow() -> String {
+    let auction = auction();
+    let words = [EXTENSION_FLAG_AUCTION as u64, auction.start_time, auction.duration, auction.initial_rate_bump]
+        .map(u64_to_u256);
+    let immutables = Immutables { extension: Some(words.concat()), ..immutables(1, 1_000, 100) };
+    create(immutables).unwrap()
+}
+
+fn withdraw(escrow_id: &str) -> Result<WithdrawalReceipt, EscrowError> {
+    mock::run(withdraw_with_secret(escrow_id.to_string(), secret(1)))
+}
+
+#[test]
+fn price_decays_from_the_bumped_start_price_to_the_amount() {
+    let auction = auction();
+    let start = auction.start_time;
+    assert_eq!(auction.price_at(1_000, start - 50), 1_100);
+    assert_eq!(auction.price_at(1_000, start), 1_100);
+    assert_eq!(auction.price_at(1_000, start + 300), 1_050);
+    assert_eq!(auction.price_at(1_000, start + 600), 1_000);
+    assert_eq!(auction.price_at(1_000, start + 6_000), 1_000);
+}
+
+#[test]
+fn deposit_covering_the_start_price_pays_the_current_price_and_returns_the_rest() {
+    let depositor = mock::controller();
+    with_icp_ledger(depositor);
+    let escrow_id = create_auction_escrow();
+    deposit(&escrow_id, depositor).unwrap();
+    assert_eq!(escrow(&escrow_id).deposited_amount, 1_100);
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 1_200);
+    let depositor_before = mock::balance(icp_ledger(), Account::from(depositor));
+
+    mock::advance(400);
+    let receipt = withdraw(&escrow_id).unwrap();
+
+    assert_eq!(receipt.amount_transferred, 1_040u64);
+    assert_eq!(receipt.auction_surplus_returned, 40u64);
+    assert_eq!(receipt.fee, 10u64);
+    assert_eq!(receipt.recipient, Account::from(principal(1)));
+    assert_eq!(receipt.safety_deposit_to, Account::from(depositor));
+    assert_eq!(mock::balance(icp_ledger(), Account::from(principal(1))), 1_040);
+    // Surplus and safety deposit back to the depositor, each less the ledger fee
+    assert_eq!(mock::balance(icp_ledger(), Account::from(depositor)), depositor_before + 40 + 90);
+}
+
+#[test]
+fn deposit_covering_only_the_end_price_waits_for_the_price_to_decay() {
+    with_icp_ledger(principal(7));
+    let escrow_id = create_auction_escrow();
+    mock::mint(icp_ledger(), deposit_account(&escrow_id), 1_100);
+    assert!(mock::run(refresh_funding(escrow_id.clone())).unwrap());
+    assert_eq!(escrow(&escrow_id).deposited_amount, 1_000);
+
+    mock::advance(400);
+    assert_eq!(withdraw(&escrow_id).err(), Some(EscrowError::InsufficientBalance(50)));
+    assert!(!escrow(&escrow_id).withdrawn);
+
+    mock::advance(300);
+    let receipt = withdraw(&escrow_id).unwrap();
+    assert_eq!(receipt.amount_transferred, 990u64);
+    assert_eq!(receipt.auction_surplus_returned, 0u64);
+}
+
+#[test]
+fn unfunded_auction_escrow_cannot_be_withdrawn() {
+    with_icp_ledger(principal(7));
+    let escrow_id = create_auction_escrow();
+
+    mock::advance(700);
+    assert_eq!(withdraw(&escrow_id).err(), Some(EscrowError::NotFunded));
+}
@@ -0,0 +1,68 @@
+use super::*;
+
+fn audited_escrows(events: &[AuditEntry]) -> Vec<Option<String>> {
+    events.iter().map(|entry| entry.escrow_id.clone()).collect()
+}
+
+#[test]
+fn audit_log_filters_by_event_kind_in_sequence_order() {
+    let first = create(immutables(1, 1_000, 100)).unwrap();
+    set_dry_run_mode(true).unwrap();
+    let second = create(immutables(2, 1_000, 100)).unwrap();
+
+    let created = get_audit_log_by_event(AuditEvent::EscrowCreated, 0, 10);
+    assert_eq!(audited_escrows(&created), vec![Some(first), Some(second)]);
+    assert!(created.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+    let changes = get_audit_log_by_event(AuditEvent::ConfigChanged, 0, 10);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].details, "dry_run_mode = true");
+    assert!(get_audit_log_by_event(AuditEvent::Rescued, 0, 10).is_empty());
+}
+
+#[test]
+fn audit_log_by_event_pages_through_matches() {
+    let ids: Vec<String> = (1..=5).map(|seed| create(immutables(seed, 1_000, 100)).unwrap()).collect();
+    set_dry_run_mode(true).unwrap();
+
+    let page = |offset, limit| audited_escrows(&get_audit_log_by_event(AuditEvent::EscrowCreated, offset, limit));
+    assert_eq!(page(0, 2), vec![Some(ids[0].clone()), Some(ids[1].clone())]);
+    assert_eq!(page(2, 2), vec![Some(ids[2].clone()), Some(ids[3].clone())]);
+    assert_eq!(page(4, 2), vec![Some(ids[4].clone())]);
+    assert!(page(5, 2).is_empty());
+    assert_eq!(page(0, 0), Vec::<Option<String>>::new());
+}
+
+#[test]
+fn audit_log_page_size_is_capped() {
+    for seed in 1..=(MAX_PAGE_SIZE as u8 + 5) {
+        create(immutables(seed, 1_000, 100)).unwrap();
+    }
+    assert_eq!(get_audit_log_by_event(AuditEvent::EscrowCreated, 0, u64::MAX).len() as u64, MAX_PAGE_SIZE);
+}
+
+#[test]
+fn escrow_state_at_an_earlier_sequence_shows_the_earlier_status() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let before = create(immutables(2, 1_000, 100)).unwrap();
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    mock::advance(100);
+    mock::run(withdraw_with_secret(escrow_id.clone(), secret(1))).unwrap();
+
+    let seq_of = |event| get_audit_log_by_event(event, 0, MAX_PAGE_SIZE).into_iter()
+        .find(|entry| entry.escrow_id.as_deref() == Some(escrow_id.as_str()))
+        .unwrap()
+        .seq;
+    let (created, withdrawn) = (seq_of(AuditEvent::EscrowCreated), seq_of(AuditEvent::Withdrawn));
+    let status_at = |seq| get_escrow_state_at(escrow_id.clone(), seq).map(|snapshot| (snapshot.status, snapshot.filled_amount));
+
+    assert_eq!(status_at(created - 1), None);
+    assert_eq!(status_at(created), Some((EscrowStatus::Pending, 0)));
+    assert_eq!(status_at(withdrawn - 1), Some((EscrowStatus::Pending, 0)));
+    assert_eq!(status_at(withdrawn), Some((EscrowStatus::Withdrawn, 1_000)));
+    assert_eq!(status_at(u64::MAX), Some((EscrowStatus::Withdrawn, 1_000)));
+    // Other escrows' history is separate
+    assert_eq!(get_escrow_state_at(before, u64::MAX).unwrap().status, EscrowStatus::Pending);
+    assert_eq!(get_escrow_state_at("missing".to_string(), u64::MAX), None);
+}
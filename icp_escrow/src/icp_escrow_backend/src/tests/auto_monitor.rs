@@ -0,0 +1,83 @@
+use super::*;
+
+fn create_monitored(immutables: Immutables, token_ledger: Option<Principal>) -> String {
+    mock::run(create_and_monitor(immutables, principal(1), token_ledger, EVM_CHAIN, EVM_ESCROW.to_string(), None)).unwrap()
+}
+
+fn is_monitored(escrow_id: &str) -> bool {
+    AUTO_MONITOR_TIMERS.with(|timers| timers.borrow().contains_key(escrow_id))
+}
+
+#[test]
+fn create_and_monitor_stores_the_escrow_with_its_monitor_running() {
+    let ledger = principal(40);
+    let token = register_token(ledger);
+    let escrow_id = create_monitored(Immutables { token, ..immutables(1, 1_000, 100) }, Some(ledger));
+
+    let escrow = escrow(&escrow_id);
+    assert!(escrow.auto_withdraw_enabled);
+    assert_eq!(escrow.auto_monitor_interval_secs, Some(DEFAULT_AUTO_MONITOR_INTERVAL_SECS));
+    assert_eq!(escrow.token_ledger, Some(ledger));
+    assert_eq!(escrow.safety_deposit_ledger, Some(ledger));
+    assert!(is_monitored(&escrow_id));
+    assert_eq!(mock::pending_timers(), 1);
+
+    serve_evm_chain(500, vec![]);
+    mock::advance(DEFAULT_AUTO_MONITOR_INTERVAL_SECS);
+    assert!(mock::rpc_calls() > 0);
+    assert!(is_monitored(&escrow_id));
+}
+
+#[test]
+fn create_and_monitor_rejects_escrows_it_cannot_monitor() {
+    let result = mock::run(create_and_monitor(immutables(1, 1_000, 100), principal(1), None, 0, EVM_ESCROW.to_string(), None));
+    assert_eq!(result, Err(EscrowError::NoEvmConfig));
+    assert!(ESCROWS.with(|escrows| escrows.borrow().is_empty()));
+    assert_eq!(mock::pending_timers(), 0);
+}
+
+#[test]
+fn monitor_withdraws_once_the_reveal_is_seen_and_stops() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create_monitored(immutables(1, 1_000, 100), None);
+    deposit(&escrow_id, payer).unwrap();
+    serve_evm_chain(500, vec![]);
+
+    mock::advance(DEFAULT_AUTO_MONITOR_INTERVAL_SECS);
+    assert!(!escrow(&escrow_id).withdrawn);
+    assert!(is_monitored(&escrow_id));
+
+    // Revealed once the withdrawal window opened at 100s
+    serve_evm_chain(510, vec![reveal_log(1, 505)]);
+    mock::advance(DEFAULT_AUTO_MONITOR_INTERVAL_SECS);
+    assert!(escrow(&escrow_id).withdrawn);
+    assert_eq!(mock::balance(icp_ledger(), Account::from(principal(1))), 1_000 - 10);
+    assert!(!is_monitored(&escrow_id));
+    assert_eq!(mock::pending_timers(), 0);
+}
+
+#[test]
+fn toggling_auto_withdraw_starts_and_stops_the_monitor_timer() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    let toggle = |enabled: bool| mock::run(set_auto_withdraw(escrow_id.clone(), enabled)).unwrap();
+
+    toggle(false);
+    assert!(!is_monitored(&escrow_id));
+    assert_eq!(mock::pending_timers(), 0);
+
+    toggle(true);
+    assert!(is_monitored(&escrow_id));
+    assert_eq!(mock::pending_timers(), 1);
+    assert_eq!(escrow(&escrow_id).auto_monitor_interval_secs, Some(DEFAULT_AUTO_MONITOR_INTERVAL_SECS));
+    // Enabling again keeps the single running timer
+    toggle(true);
+    assert_eq!(mock::pending_timers(), 1);
+
+    toggle(false);
+    assert!(!is_monitored(&escrow_id));
+    assert_eq!(mock::pending_timers(), 0);
+    serve_evm_chain(500, vec![]);
+    mock::advance(DEFAULT_AUTO_MONITOR_INTERVAL_SECS);
+    assert_eq!(mock::rpc_calls(), 0);
+}
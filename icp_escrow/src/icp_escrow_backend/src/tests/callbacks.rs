@@ -0,0 +1,49 @@
+use super::*;
+
+const WATCHER: &str = "on_escrow_event";
+
+/// Funded escrow with a callback registered; its withdrawal window opens 100s in
+fn watched_escrow() -> String {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    register_escrow_callback(escrow_id.clone(), principal(60), WATCHER.to_string()).unwrap();
+    escrow_id
+}
+
+#[test]
+fn notification_fires_when_the_withdrawal_window_opens() {
+    watched_escrow();
+    assert_eq!(mock::pending_timers(), 1);
+
+    mock::advance(99);
+    assert!(mock::notifications().is_empty());
+    mock::advance(1);
+    assert_eq!(mock::notifications(), vec![(principal(60), WATCHER.to_string())]);
+    assert_eq!(mock::pending_timers(), 0);
+}
+
+#[test]
+fn early_cancellation_drops_the_notification() {
+    let escrow_id = watched_escrow();
+    // The kill switch lets the escrow be cancelled before its withdrawal window
+    emergency_unlock().unwrap();
+
+    mock::advance(50);
+    mock::run(cancel_escrow(escrow_id)).unwrap();
+    assert_eq!(mock::pending_timers(), 0);
+    mock::advance(100);
+    assert!(mock::notifications().is_empty());
+}
+
+#[test]
+fn no_notification_once_the_window_has_passed() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::advance(100);
+
+    register_escrow_callback(escrow_id, principal(60), WATCHER.to_string()).unwrap();
+    assert_eq!(mock::pending_timers(), 0);
+}
@@ -0,0 +1,112 @@
+use super::*;
+
+fn cancel_as(caller: Principal, escrow_id: &str, public: bool) -> Result<RefundReceipt, EscrowError> {
+    mock::set_caller(caller);
+    let result = mock::run(execute_cancellation(escrow_id.to_string(), public));
+    mock::set_caller(mock::controller());
+    result
+}
+
+fn why_blocked_as(caller: Principal, escrow_id: &str, action: Action) -> BlockReason {
+    mock::set_caller(caller);
+    let reason = why_blocked(escrow_id.to_string(), action).unwrap();
+    mock::set_caller(mock::controller());
+    reason
+}
+
+/// Destination escrow created and funded by `taker` (see `with_icp_ledger`): DstCancellation 300s in, public cancellation
+/// an hour later
+fn dst_escrow(seed: u8, taker: Principal) -> String {
+    mock::set_caller(taker);
+    let escrow_id = create(immutables(seed, 1_000, 100)).unwrap();
+    deposit(&escrow_id, taker).unwrap();
+    escrow_id
+}
+
+#[test]
+fn dst_cancellation_stays_taker_only_until_the_public_boundary() {
+    let (taker, stranger) = (principal(20), principal(30));
+    with_icp_ledger(taker);
+    let first = dst_escrow(1, taker);
+    let second = dst_escrow(2, taker);
+    let public_opens = mock::GENESIS_SECS + 300 + DEFAULT_DST_PUBLIC_CANCELLATION_DELAY_SECS;
+    assert_eq!(escrow(&first).public_cancellation_time(), public_opens);
+
+    // Past SrcPublicCancellation (40s) and DstCancellation (300s), inside the private window
+    mock::advance(300);
+    assert_eq!(cancel_as(stranger, &first, false).err(), Some(EscrowError::Unauthorized));
+    assert_eq!(cancel_as(stranger, &first, true).err(), Some(EscrowError::TimelockNotMet));
+    assert!(matches!(why_blocked_as(stranger, &first, Action::Cancel), BlockReason::Unauthorized));
+    assert!(matches!(
+        why_blocked_as(stranger, &first, Action::PublicCancel),
+        BlockReason::DstPublicCancellation { deadline, .. } if deadline == public_opens
+    ));
+    assert!(matches!(why_blocked_as(taker, &first, Action::Cancel), BlockReason::NotBlocked));
+    assert!(cancel_as(taker, &first, false).is_ok());
+
+    mock::advance(DEFAULT_DST_PUBLIC_CANCELLATION_DELAY_SECS);
+    assert!(matches!(why_blocked_as(stranger, &second, Action::PublicCancel), BlockReason::NotBlocked));
+    assert!(cancel_as(stranger, &second, true).is_ok());
+}
+
+#[test]
+fn taker_cannot_cancel_before_dst_cancellation() {
+    let taker = principal(20);
+    with_icp_ledger(taker);
+    let escrow_id = dst_escrow(1, taker);
+
+    mock::advance(299);
+    assert_eq!(cancel_as(taker, &escrow_id, false).err(), Some(EscrowError::TimelockNotMet));
+    assert!(matches!(
+        why_blocked_as(taker, &escrow_id, Action::Cancel),
+        BlockReason::Timelock { stage: TimelockStage::DstCancellation, seconds_remaining: 1, .. }
+    ));
+}
+
+#[test]
+fn configured_delay_applies_to_new_dst_escrows_only() {
+    let taker = principal(20);
+    with_icp_ledger(taker);
+    let before = dst_escrow(1, taker);
+    set_dst_public_cancellation_delay(60).unwrap();
+    let after = dst_escrow(2, taker);
+
+    assert_eq!(escrow(&before).public_cancellation_time(), mock::GENESIS_SECS + 300 + DEFAULT_DST_PUBLIC_CANCELLATION_DELAY_SECS);
+    assert_eq!(escrow(&after).public_cancellation_time(), mock::GENESIS_SECS + 360);
+    mock::advance(360);
+    assert!(cancel_as(principal(30), &after, true).is_ok());
+}
+
+#[test]
+fn src_public_cancellation_keeps_its_packed_stage() {
+    let taker = principal(20);
+    with_icp_ledger(taker);
+    let escrow_id = dst_escrow(1, taker);
+    update_escrow(&escrow_id, |escrow| escrow.side = EscrowSide::Src);
+    assert_eq!(escrow(&escrow_id).public_cancellation_time(), mock::GENESIS_SECS + 40);
+
+    mock::advance(30);
+    assert!(matches!(
+        why_blocked_as(principal(30), &escrow_id, Action::PublicCancel),
+        BlockReason::Timelock { stage: TimelockStage::SrcPublicCancellation, seconds_remaining: 10, .. }
+    ));
+    mock::advance(10);
+    assert!(cancel_as(principal(30), &escrow_id, true).is_ok());
+}
+
+#[test]
+fn kill_switch_opens_the_private_path_only() {
+    let (taker, stranger) = (principal(20), principal(30));
+    with_icp_ledger(taker);
+    let escrow_id = dst_escrow(1, taker);
+    emergency_unlock().unwrap();
+
+    // A third party cannot claim the safety deposit early
+    assert_eq!(cancel_as(stranger, &escrow_id, true).err(), Some(EscrowError::TimelockNotMet));
+    assert!(matches!(why_blocked_as(stranger, &escrow_id, Action::PublicCancel), BlockReason::DstPublicCancellation { .. }));
+    assert!(matches!(why_blocked_as(taker, &escrow_id, Action::Cancel), BlockReason::NotBlocked));
+
+    let receipt = cancel_as(taker, &escrow_id, false).unwrap();
+    assert_eq!(receipt.safety_deposit_to, Account::from(taker));
+    assert_eq!(receipt.refunded_amount, 990u64);
+}
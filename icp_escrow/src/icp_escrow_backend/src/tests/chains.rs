@@ -0,0 +1,34 @@
+use super::*;
+
+#[test]
+fn known_chain_ids_resolve_to_their_metadata() {
+    assert_eq!(get_chain_info(1), Some(ChainInfo {
+        chain_id: 1,
+        name: "Ethereum".to_string(),
+        native_currency: "ETH".to_string(),
+        default_confirmations: 12,
+        avg_block_time_ms: 12_000,
+    }));
+    assert_eq!(get_chain_info(BASE_SEPOLIA_CHAIN_ID).unwrap().name, "Base Sepolia");
+}
+
+#[test]
+fn unknown_chain_ids_have_no_metadata() {
+    assert_eq!(get_chain_info(0), None);
+    assert_eq!(get_chain_info(999_999), None);
+}
+
+#[test]
+fn escrows_on_unknown_chains_are_monitored_on_their_own_chain() {
+    let escrow_id = insert_escrow(
+        immutables(1, 1_000, 100), principal(1), None, None, 999_999, EVM_ESCROW.to_string(), None, None,
+    ).unwrap();
+    let mut chain = evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    mock::set_rpc(move |source, request| {
+        assert!(matches!(source, RpcSource::Chain(999_999)), "{:?}", source);
+        chain(source, request)
+    });
+
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id));
+    assert!(matches!(outcome, Ok(MonitorOutcome::SecretFound(found)) if found == secret(1)));
+}
@@ -0,0 +1,42 @@
+use super::*;
+
+fn round_trip(escrow_id: &str) -> CompactEscrow {
+    let bytes = get_escrow_state_compact(escrow_id.to_string()).expect("escrow exists");
+    assert_eq!(bytes.len(), COMPACT_ESCROW_LEN);
+    decode_escrow_state_compact(bytes).expect("valid encoding")
+}
+
+#[test]
+fn compact_state_round_trips_the_escrow_fields() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    update_escrow(&escrow_id, |e| {
+        e.needs_attention = true;
+        e.secret_revealed_at = Some(current_time_seconds());
+    });
+
+    let stored = escrow(&escrow_id);
+    let deadlines = get_key_deadlines(escrow_id.clone()).unwrap();
+    assert_eq!(round_trip(&escrow_id), CompactEscrow {
+        status: EscrowStatus::Pending,
+        flags: COMPACT_FLAG_FUNDED | COMPACT_FLAG_AUTO_WITHDRAW | COMPACT_FLAG_NEEDS_ATTENTION | COMPACT_FLAG_SECRET_REVEALED,
+        amount: 1_000,
+        deposited_amount: stored.deposited_amount,
+        withdrawal_opens: deadlines.withdrawal_opens,
+        public_withdrawal_opens: deadlines.public_withdrawal_opens,
+        cancellation_opens: deadlines.cancellation_opens,
+    });
+    assert!(stored.deposited_amount > 0);
+
+    update_escrow(&escrow_id, |e| {
+        e.withdrawn = true;
+        e.auto_withdraw_enabled = false;
+        e.needs_attention = false;
+    });
+    let withdrawn = round_trip(&escrow_id);
+    assert_eq!(withdrawn.status, EscrowStatus::Withdrawn);
+    assert_eq!(withdrawn.flags, COMPACT_FLAG_FUNDED | COMPACT_FLAG_SECRET_REVEALED);
+    assert_eq!(get_escrow_state_compact("missing".to_string()), None);
+}
@@ -0,0 +1,52 @@
+use super::*;
+
+const PROVIDER_A: &str = "https://a.example";
+const PROVIDER_B: &str = "https://b.example";
+
+/// Consensus of both providers, where only A has seen the reveal
+fn disagreeing_providers() -> String {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    set_rpc_consensus(2, vec![PROVIDER_A.to_string(), PROVIDER_B.to_string()]).unwrap();
+    let mut with_reveal = evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    let mut without_reveal = evm_chain(1_000, vec![]);
+    mock::set_rpc(move |source, request| match source {
+        RpcSource::Custom { url } if url == PROVIDER_A => with_reveal(source, request),
+        _ => without_reveal(source, request),
+    });
+    escrow_id
+}
+
+#[test]
+fn inconsistent_results_list_each_provider_response() {
+    let responses = [
+        ("[1]".to_string(), serde_json::json!([1]), vec![RpcSource::Custom { url: PROVIDER_A.to_string() }]),
+        ("[]".to_string(), serde_json::json!([]), vec![RpcSource::Chain(1), RpcSource::Custom { url: PROVIDER_B.to_string() }]),
+    ];
+    assert_eq!(inconsistent_results(&responses), RpcError::Inconsistent(vec![
+        format!("{}: [1]", PROVIDER_A),
+        format!("chain 1, {}: []", PROVIDER_B),
+    ]));
+}
+
+#[test]
+fn disagreeing_providers_are_retried_later_by_default() {
+    let escrow_id = disagreeing_providers();
+
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id.clone()));
+    assert!(matches!(outcome, Ok(MonitorOutcome::Cooldown { .. })));
+    assert_eq!(escrow(&escrow_id).secret, None);
+}
+
+#[test]
+fn disagreeing_providers_fail_monitoring_under_the_fail_policy() {
+    let escrow_id = disagreeing_providers();
+    set_inconsistent_rpc_policy(InconsistentRpcPolicy::Fail).unwrap();
+
+    let error = mock::run(monitor_evm_secret_revelation(escrow_id)).unwrap_err();
+    let EscrowError::Rpc(RpcError::Inconsistent(responses)) = error else {
+        panic!("expected inconsistent results, got {:?}", error);
+    };
+    assert_eq!(responses.len(), 2);
+    assert!(responses.iter().any(|response| response.starts_with(PROVIDER_A)), "{:?}", responses);
+    assert!(responses.iter().any(|response| response.starts_with(PROVIDER_B)), "{:?}", responses);
+}
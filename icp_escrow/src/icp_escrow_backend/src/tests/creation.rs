@@ -0,0 +1,115 @@
+use super::*;
+
+#[test]
+fn safety_deposit_floor_applies_per_ledger() {
+    set_min_safety_deposit(None, candid::Nat::from(100u64)).unwrap();
+    assert_eq!(get_min_safety_deposit(None), 100u64);
+
+    assert_eq!(create(immutables(1, 1_000, 99)), Err(EscrowError::InvalidInput));
+    assert!(create(immutables(2, 1_000, 100)).is_ok());
+    assert!(create(immutables(3, 1_000, 101)).is_ok());
+
+    // A floor on another ledger leaves native-ICP escrows alone
+    set_min_safety_deposit(None, candid::Nat::from(0u64)).unwrap();
+    set_min_safety_deposit(Some(principal(40)), candid::Nat::from(1_000u64)).unwrap();
+    assert_eq!(get_min_safety_deposit(None), 0u64);
+    assert!(create(immutables(4, 1_000, 1)).is_ok());
+}
+
+#[test]
+fn peeked_escrow_id_is_the_next_one_assigned() {
+    let peeked = peek_next_escrow_id();
+    assert_eq!(peek_next_escrow_id(), peeked);
+    assert_eq!(create(immutables(1, 1_000, 100)).unwrap(), peeked);
+    assert_ne!(peek_next_escrow_id(), peeked);
+    let peeked = peek_next_escrow_id();
+    assert_eq!(create(immutables(2, 1_000, 100)).unwrap(), peeked);
+}
+
+#[test]
+fn hashlock_equal_to_the_order_hash_is_rejected_on_every_path() {
+    let copy_pasted = |seed: u8| Immutables { hashlock: [seed; 32], ..immutables(seed, 1_000, 100) };
+    let request = |immutables: Immutables| CreateEscrowRequest {
+        immutables,
+        icp_recipient: principal(1),
+        token_ledger: None,
+        safety_deposit_ledger: None,
+        evm_chain_id: EVM_CHAIN,
+        evm_escrow_address: EVM_ESCROW.to_string(),
+        rpc_override: None,
+        lookback_blocks: None,
+    };
+
+    assert_eq!(create(copy_pasted(1)), Err(EscrowError::InvalidInput));
+    let source = mock::run(create_source_escrow(
+        copy_pasted(2), principal(2), principal(1), None, EVM_CHAIN, EVM_ESCROW.to_string(),
+    ));
+    assert_eq!(source, Err(EscrowError::InvalidInput));
+    let batch = batch_create_escrows(vec![request(copy_pasted(3)), request(immutables(4, 1_000, 100))]);
+    assert_eq!(batch[0], Err(EscrowError::InvalidInput));
+    assert!(batch[1].is_ok());
+    assert!(create(immutables(5, 1_000, 100)).is_ok());
+}
+
+#[test]
+fn order_history_follows_a_cancelled_and_recreated_order() {
+    let first = create(immutables(1, 1_000, 100)).unwrap();
+    create(immutables(2, 1_000, 100)).unwrap();
+    mock::advance(300);
+    mock::run(cancel_escrow(first.clone())).unwrap();
+    mock::advance(10);
+    let recreated = create(immutables(1, 1_000, 100)).unwrap();
+
+    assert_eq!(get_order_history([1; 32]), vec![
+        (first, EscrowStatus::Cancelled, mock::GENESIS_SECS),
+        (recreated, EscrowStatus::Pending, mock::GENESIS_SECS + 310),
+    ]);
+    assert!(get_order_history([3; 32]).is_empty());
+}
+
+#[test]
+fn creation_stops_at_the_active_escrow_cap_until_one_completes() {
+    set_max_active_escrows(Some(2)).unwrap();
+    let first = create(immutables(1, 1_000, 100)).unwrap();
+    create(immutables(2, 1_000, 100)).unwrap();
+    assert_eq!(create(immutables(3, 1_000, 100)), Err(EscrowError::CapacityReached));
+
+    mock::advance(300);
+    mock::run(cancel_escrow(first)).unwrap();
+    archive_completed(0).unwrap();
+    assert!(create(immutables(3, 1_000, 100)).is_ok());
+    assert_eq!(create(immutables(4, 1_000, 100)), Err(EscrowError::CapacityReached));
+
+    set_max_active_escrows(None).unwrap();
+    assert!(create(immutables(4, 1_000, 100)).is_ok());
+}
+
+#[test]
+fn zero_amount_escrows_need_dry_run_mode() {
+    let hex = |bytes: [u8; 32]| format!("0x{}", hex::encode(bytes));
+    let with_hex_secret = |seed: u8| mock::run(create_escrow_with_hex_secret(
+        hex([seed; 32]), hex(keccak256(&secret(seed))),
+        "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_string(),
+        "0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb".to_string(),
+        0, 100, 300, principal(1), EVM_CHAIN, EVM_ESCROW.to_string(), None,
+    ));
+
+    assert_eq!(create(immutables(1, 0, 100)), Err(EscrowError::InvalidInput));
+    assert_eq!(with_hex_secret(2), Err(EscrowError::InvalidInput));
+
+    set_dry_run_mode(true).unwrap();
+    assert!(create(immutables(1, 0, 100)).is_ok());
+    assert!(with_hex_secret(2).is_ok());
+}
+
+#[test]
+fn safety_deposit_above_amount_is_rejected_only_when_enabled() {
+    assert!(create(immutables(1, 100, 500)).is_ok());
+
+    set_reject_safety_deposit_above_amount(true).unwrap();
+    assert_eq!(create(immutables(2, 100, 500)), Err(EscrowError::InvalidInput));
+    assert!(create(immutables(3, 500, 500)).is_ok());
+
+    set_reject_safety_deposit_above_amount(false).unwrap();
+    assert!(create(immutables(4, 100, 500)).is_ok());
+}
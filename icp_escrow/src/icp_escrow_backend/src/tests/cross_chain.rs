@@ -0,0 +1,54 @@
+use super::*;
+
+#[test]
+fn immutables_comparison_names_the_differing_field() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    let stored = get_escrow_immutables(escrow_id.clone()).unwrap();
+    let compare = |expected: Immutables, ignore_deployed_at| immutables_match(escrow_id.clone(), expected, ignore_deployed_at).unwrap();
+
+    let exact = compare(stored.clone(), false);
+    assert!(exact.matches);
+    assert!(exact.differing_fields.is_empty());
+
+    let other_amount = compare(Immutables { amount: u64_to_u256(1_001), ..stored.clone() }, false);
+    assert!(!other_amount.matches);
+    assert_eq!(other_amount.differing_fields, vec!["amount".to_string()]);
+
+    // The caller's copy isn't anchored to this canister's deployment time
+    let unanchored = immutables(1, 1_000, 100);
+    assert_eq!(compare(unanchored.clone(), false).differing_fields, vec!["timelocks".to_string()]);
+    assert!(compare(unanchored, true).matches);
+    assert_eq!(
+        immutables_match("missing".to_string(), stored, false).err(),
+        Some(EscrowError::EscrowNotFound),
+    );
+}
+
+#[test]
+fn default_subaccount_accounts_round_trip_through_bytes32() {
+    for owner in [principal(1), Principal::from_slice(&[7; 29]), Principal::anonymous()] {
+        let account = Account { owner, subaccount: None };
+        assert_eq!(bytes32_to_account(account_to_bytes32(account)), Some(account));
+    }
+    // An explicit all-zero subaccount is the default one
+    let zeroed = Account { owner: principal(1), subaccount: Some([0; 32]) };
+    assert_eq!(account_to_bytes32(zeroed), account_to_bytes32(Account::from(principal(1))));
+}
+
+#[test]
+fn distinct_accounts_map_to_distinct_bytes32() {
+    let accounts = [
+        Account::from(principal(1)),
+        Account::from(principal(2)),
+        Account { owner: principal(1), subaccount: Some([1; 32]) },
+        Account { owner: principal(1), subaccount: Some([2; 32]) },
+        Account { owner: principal(2), subaccount: Some([1; 32]) },
+    ];
+    let encoded: std::collections::HashSet<[u8; 32]> = accounts.iter().map(|a| account_to_bytes32(*a)).collect();
+    assert_eq!(encoded.len(), accounts.len());
+
+    // Hashed accounts are one-way
+    let hashed = account_to_bytes32(accounts[2]);
+    assert_eq!(hashed[0], HASHED_ACCOUNT_TAG);
+    assert_eq!(bytes32_to_account(hashed), None);
+}
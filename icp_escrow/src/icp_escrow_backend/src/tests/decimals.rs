@@ -0,0 +1,48 @@
+use super::*;
+
+const ONE_ETHER: u64 = 1_000_000_000_000_000_000;
+
+#[test]
+fn scales_18_decimal_amounts_to_8_and_flags_precision_loss() {
+    assert_eq!(scale_amount(u64_to_u256(3 * ONE_ETHER / 2), 18, 8), Ok(candid::Nat::from(150_000_000u64)));
+    assert_eq!(scale_amount(u64_to_u256(10_000_000_000), 18, 8), Ok(candid::Nat::from(1u64)));
+    assert_eq!(scale_amount(u64_to_u256(ONE_ETHER + 1), 18, 8), Err(EscrowError::PrecisionLoss));
+    assert_eq!(scale_amount(u64_to_u256(1), 8, 18), Ok(candid::Nat::from(10_000_000_000u64)));
+    assert_eq!(scale_amount(u64_to_u256(42), 8, 8), Ok(candid::Nat::from(42u64)));
+}
+
+#[test]
+fn escrow_keeps_the_decimal_context_and_transfers_the_scaled_amount() {
+    let ledger = principal(40);
+    let token = register_token(ledger);
+    set_token_decimals(format!("0x{}", hex::encode(&token[12..])), 18, 8).unwrap();
+    let create_token = |seed: u8, amount: u64| insert_escrow(
+        Immutables { token, ..immutables(seed, amount, 100) },
+        principal(1), Some(ledger), None, EVM_CHAIN, EVM_ESCROW.to_string(), None, None,
+    );
+
+    let escrow_id = create_token(1, 2 * ONE_ETHER).unwrap();
+    assert_eq!(escrow(&escrow_id).decimals, DecimalContext { evm: 18, icp: 8 });
+    assert_eq!(escrow(&escrow_id).icp_amount(), 200_000_000);
+    assert_eq!(create_token(2, ONE_ETHER + 1), Err(EscrowError::PrecisionLoss));
+}
+
+#[test]
+fn safety_deposit_check_compares_ledger_units_of_the_same_token() {
+    let ledger = principal(40);
+    let token = register_token(ledger);
+    // 6-decimal EVM token held as an 8-decimal ledger token
+    set_token_decimals(format!("0x{}", hex::encode(&token[12..])), 6, 8).unwrap();
+    set_reject_safety_deposit_above_amount(true).unwrap();
+    let create_token = |seed: u8, amount: u64, safety_deposit: u64, safety_deposit_ledger: Option<Principal>| insert_escrow(
+        Immutables { token, ..immutables(seed, amount, safety_deposit) },
+        principal(1), Some(ledger), safety_deposit_ledger, EVM_CHAIN, EVM_ESCROW.to_string(), None, None,
+    );
+
+    // One token is 100 000 000 ledger units, whatever its raw EVM amount
+    assert!(create_token(1, 1_000_000, 5_000_000, Some(ledger)).is_ok());
+    assert!(create_token(2, 1_000_000, 100_000_000, Some(ledger)).is_ok());
+    assert_eq!(create_token(3, 1_000_000, 100_000_001, Some(ledger)), Err(EscrowError::InvalidInput));
+    // A deposit in another token isn't comparable
+    assert!(create_token(4, 1_000_000, 100_000_001, None).is_ok());
+}
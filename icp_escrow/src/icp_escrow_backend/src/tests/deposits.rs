@@ -0,0 +1,95 @@
+use super::*;
+
+/// Escrow of 1 000 units of a mapped token, with its 100 safety deposit in ICP
+fn token_escrow_with_icp_safety_deposit(ledger: Principal) -> String {
+    let token = register_token(ledger);
+    insert_escrow(
+        Immutables { token, ..immutables(1, 1_000, 100) },
+        principal(1), Some(ledger), None, EVM_CHAIN, EVM_ESCROW.to_string(), None, None,
+    ).unwrap()
+}
+
+#[test]
+fn retry_after_a_failed_safety_deposit_pull_does_not_pull_the_swap_token_again() {
+    let payer = principal(7);
+    let ledger = principal(40);
+    with_icp_ledger(payer);
+    let escrow_id = token_escrow_with_icp_safety_deposit(ledger);
+    mock::mint(ledger, Account::from(payer), 10_000);
+    mock::fail_ledger_method(icp_ledger(), Some("icrc2_transfer_from"));
+
+    assert_eq!(deposit(&escrow_id, payer), Err(EscrowError::TokenTransferFailed));
+    assert!(!escrow(&escrow_id).funded);
+    assert_eq!(mock::balance(ledger, deposit_account(&escrow_id)), 1_000);
+
+    mock::fail_ledger_method(icp_ledger(), None);
+    deposit(&escrow_id, payer).unwrap();
+
+    assert!(escrow(&escrow_id).funded);
+    assert_eq!(mock::balance(ledger, deposit_account(&escrow_id)), 1_000);
+    assert_eq!(mock::balance(ledger, Account::from(payer)), 10_000 - 1_000 - 10);
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 100);
+}
+
+#[test]
+fn deposit_tops_up_a_partly_prefunded_subaccount() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::mint(icp_ledger(), deposit_account(&escrow_id), 600);
+
+    deposit(&escrow_id, payer).unwrap();
+
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 1_100);
+    assert_eq!(mock::balance(icp_ledger(), Account::from(payer)), 1_000_000_000 - 500 - 10);
+}
+
+#[test]
+fn funded_escrows_reject_another_deposit() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+
+    assert_eq!(deposit(&escrow_id, payer), Err(EscrowError::InvalidInput));
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 1_100);
+}
+
+fn funding_status(escrow_id: &str) -> FundingStatus {
+    mock::run(get_funding_status(escrow_id.to_string())).unwrap()
+}
+
+#[test]
+fn funding_status_reports_the_shortfall_from_the_live_balance() {
+    with_icp_ledger(principal(7));
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+
+    let unfunded = funding_status(&escrow_id);
+    assert_eq!((unfunded.required, unfunded.deposited, unfunded.shortfall), (1_100u64.into(), 0u64.into(), 1_100u64.into()));
+    assert!(!unfunded.funded);
+    assert_eq!(unfunded.deposit_account, deposit_account(&escrow_id));
+    assert_eq!(unfunded.safety_deposit_shortfall, None);
+
+    mock::mint(icp_ledger(), deposit_account(&escrow_id), 600);
+    let partial = funding_status(&escrow_id);
+    assert_eq!((partial.deposited, partial.shortfall), (600u64.into(), 500u64.into()));
+    assert!(!partial.funded);
+
+    mock::mint(icp_ledger(), deposit_account(&escrow_id), 500);
+    let funded = funding_status(&escrow_id);
+    assert_eq!((funded.deposited, funded.shortfall), (1_100u64.into(), 0u64.into()));
+    assert!(funded.funded);
+}
+
+#[test]
+fn funding_status_reports_a_separate_safety_deposit_shortfall() {
+    let ledger = principal(40);
+    with_icp_ledger(principal(7));
+    let escrow_id = token_escrow_with_icp_safety_deposit(ledger);
+    mock::mint(ledger, deposit_account(&escrow_id), 1_000);
+
+    let status = funding_status(&escrow_id);
+    assert_eq!((status.required, status.shortfall), (1_000u64.into(), 0u64.into()));
+    assert_eq!(status.safety_deposit_shortfall, Some(100u64.into()));
+    assert!(!status.funded);
+}
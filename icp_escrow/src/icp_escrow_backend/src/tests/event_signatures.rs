@@ -0,0 +1,70 @@
+use super::*;
+
+const RENAMED: &str = "SecretUnlocked(bytes32,bytes32)";
+const OTHER_ESCROW: &str = "0x2222222222222222222222222222222222222222";
+
+fn escrow_at(seed: u8, chain_id: u64, address: &str) -> String {
+    insert_escrow(immutables(seed, 1_000, 100), principal(1), None, None, chain_id, address.to_string(), None, None).unwrap()
+}
+
+/// topic0 of the eth_getLogs request a monitor call sends for the escrow
+fn requested_event_topic(escrow_id: &str) -> String {
+    let topic = std::rc::Rc::new(RefCell::new(String::new()));
+    let seen = topic.clone();
+    let mut chain = evm_chain(1_000, vec![]);
+    mock::set_rpc(move |source, request| {
+        if request["method"] == "eth_getLogs" {
+            *seen.borrow_mut() = request["params"][0]["topics"][0].as_str().unwrap().to_string();
+        }
+        chain(source, request)
+    });
+    mock::run(monitor_evm_secret_revelation(escrow_id.to_string())).unwrap();
+    mock::advance(60);
+    let topic = topic.borrow().clone();
+    topic
+}
+
+#[test]
+fn event_signature_update_targets_active_escrows_of_the_contract_only() {
+    let targeted = escrow_at(1, EVM_CHAIN, EVM_ESCROW);
+    let other_contract = escrow_at(2, EVM_CHAIN, OTHER_ESCROW);
+    let other_chain = escrow_at(3, 10, EVM_ESCROW);
+    let completed = escrow_at(4, EVM_CHAIN, EVM_ESCROW);
+    update_escrow(&completed, |escrow| escrow.cancelled = true);
+
+    assert_eq!(update_event_signature(EVM_CHAIN, EVM_ESCROW.to_uppercase().replace("0X", "0x"), RENAMED.to_string()), Ok(1));
+    assert_eq!(escrow(&targeted).reveal_event_topic, Some(keccak256(RENAMED.as_bytes())));
+    for untouched in [&other_contract, &other_chain, &completed] {
+        assert_eq!(escrow(untouched).reveal_event_topic, None);
+    }
+    assert_eq!(requested_event_topic(&targeted), format!("0x{}", hex::encode(keccak256(RENAMED.as_bytes()))));
+    assert_eq!(requested_event_topic(&other_contract), get_event_signature());
+}
+
+#[test]
+fn event_signature_update_rejects_malformed_input_and_non_controllers() {
+    escrow_at(1, EVM_CHAIN, EVM_ESCROW);
+    let update = |address: &str, signature: &str| update_event_signature(EVM_CHAIN, address.to_string(), signature.to_string());
+    assert_eq!(update("0x1234", RENAMED), Err(EscrowError::InvalidInput));
+    assert_eq!(update(EVM_ESCROW, "SecretUnlocked"), Err(EscrowError::InvalidInput));
+    assert_eq!(update(EVM_ESCROW, "Secret Unlocked(bytes32)"), Err(EscrowError::InvalidInput));
+
+    mock::set_caller(principal(30));
+    assert_eq!(update(EVM_ESCROW, RENAMED), Err(EscrowError::Unauthorized));
+    assert_eq!(configure_event_signature(format!("0x{}", hex::encode([7u8; 32]))), Err(EscrowError::Unauthorized));
+}
+
+#[test]
+fn configured_event_topic_applies_to_escrows_without_their_own() {
+    let default_topic = escrow_at(1, EVM_CHAIN, EVM_ESCROW);
+    let own_topic = escrow_at(2, EVM_CHAIN, OTHER_ESCROW);
+    update_event_signature(EVM_CHAIN, OTHER_ESCROW.to_string(), RENAMED.to_string()).unwrap();
+    assert_eq!(get_event_signature(), format!("0x{}", hex::encode(keccak256(SECRET_REVEALED_EVENT_SIGNATURE.as_bytes()))));
+
+    let configured = format!("0x{}", hex::encode([7u8; 32]));
+    assert!(matches!(configure_event_signature("0x1234".to_string()), Err(EscrowError::InvalidHex(_))));
+    configure_event_signature(format!(" {} ", configured)).unwrap();
+    assert_eq!(get_event_signature(), configured);
+    assert_eq!(requested_event_topic(&default_topic), configured);
+    assert_eq!(requested_event_topic(&own_topic), format!("0x{}", hex::encode(keccak256(RENAMED.as_bytes()))));
+}
@@ -0,0 +1,123 @@
+use super::*;
+
+/// Extension data: the flags word followed by `words`
+fn extension(flags: u8, words: &[[u8; 32]]) -> Vec<u8> {
+    let mut data = u64_to_u256(flags as u64).to_vec();
+    for word in words {
+        data.extend_from_slice(word);
+    }
+    data
+}
+
+#[test]
+fn extension_round_trips_through_abi_encoding_and_parses() {
+    let root = [0x5a; 32];
+    let data = extension(
+        EXTENSION_FLAG_MERKLE | EXTENSION_FLAG_AUCTION | EXTENSION_FLAG_BOUND_HASHLOCK,
+        &[root, u64_to_u256(4), u64_to_u256(1_000), u64_to_u256(600), u64_to_u256(50_000)],
+    );
+    let immutables = Immutables { extension: Some(data), ..immutables(1, 1_000, 100) };
+
+    let decoded = Immutables::abi_decode(&immutables.abi_encode()).unwrap();
+    assert_eq!(decoded.extension, immutables.extension);
+    assert_eq!(decoded.parse_extension().unwrap(), OrderExtension {
+        merkle_root: Some(root),
+        parts_count: Some(4),
+        auction: Some(AuctionParams { start_time: 1_000, duration: 600, initial_rate_bump: 50_000 }),
+        hashlock_binding: HashlockBinding::SecretAndOrderHash,
+    });
+}
+
+#[test]
+fn malformed_extensions_are_rejected() {
+    let parse = |data: Vec<u8>| Immutables { extension: Some(data), ..immutables(1, 1_000, 100) }.parse_extension();
+
+    assert_eq!(parse(vec![]), Err(EscrowError::InvalidInput));
+    assert_eq!(parse(vec![0; 31]), Err(EscrowError::InvalidInput));
+    // Unknown flag, missing Merkle words, trailing word
+    assert_eq!(parse(extension(0x80, &[])), Err(EscrowError::InvalidInput));
+    assert_eq!(parse(extension(EXTENSION_FLAG_MERKLE, &[[1; 32]])), Err(EscrowError::InvalidInput));
+    assert_eq!(parse(extension(EXTENSION_FLAG_BOUND_HASHLOCK, &[[0; 32]])), Err(EscrowError::InvalidInput));
+    assert_eq!(create(Immutables { extension: Some(extension(0x80, &[])), ..immutables(1, 1_000, 100) }), Err(EscrowError::InvalidInput));
+}
+
+#[test]
+fn bound_hashlock_extension_applies_at_creation() {
+    let mut immutables = immutables(1, 1_000, 100);
+    immutables.hashlock = HashlockBinding::SecretAndOrderHash.hashlock(HashAlgo::Keccak256, &secret(1), &immutables.order_hash);
+    immutables.extension = Some(extension(EXTENSION_FLAG_BOUND_HASHLOCK, &[]));
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+
+    assert_eq!(escrow(&escrow_id).hashlock_binding, HashlockBinding::SecretAndOrderHash);
+    mock::advance(100);
+    mock::run(withdraw_with_secret(escrow_id.clone(), secret(1))).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+}
+
+#[test]
+fn merkle_extension_makes_a_partial_fill_escrow_checked_against_its_root() {
+    let (root, leaves) = two_part_tree();
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let immutables = Immutables {
+        extension: Some(extension(EXTENSION_FLAG_MERKLE, &[root, u64_to_u256(2)])),
+        ..immutables(1, 1_000, 100)
+    };
+    let escrow_id = create(immutables).unwrap();
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().parts_count, 2);
+    assert_eq!(escrow(&escrow_id).merkle_root(), root);
+    deposit(&escrow_id, payer).unwrap();
+    mock::advance(100);
+
+    mock::run(withdraw_partial(escrow_id.clone(), secret(1), vec![leaves[1]], 1)).unwrap();
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().filled_amount, 500);
+    mock::run(withdraw_partial(escrow_id.clone(), secret(2), vec![leaves[0]], 2)).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+}
+
+#[test]
+fn partial_fill_creation_rejects_a_parts_count_the_extension_contradicts() {
+    let (root, _) = two_part_tree();
+    let immutables = Immutables {
+        extension: Some(extension(EXTENSION_FLAG_MERKLE, &[root, u64_to_u256(2)])),
+        ..immutables(1, 1_000, 100)
+    };
+    let create_parts = |parts_count| mock::run(create_partial_fill_escrow(
+        immutables.clone(), parts_count, principal(1), None, EVM_CHAIN, EVM_ESCROW.to_string(),
+    ));
+
+    assert_eq!(create_parts(3), Err(EscrowError::InvalidInput));
+    let escrow_id = create_parts(2).unwrap();
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().parts_count, 2);
+
+    let zero_parts = Immutables {
+        extension: Some(extension(EXTENSION_FLAG_MERKLE, &[root, u64_to_u256(0)])),
+        ..immutables.clone()
+    };
+    assert_eq!(create(zero_parts), Err(EscrowError::InvalidInput));
+}
+
+#[test]
+fn merkle_extension_escrow_cannot_be_withdrawn_in_full_after_a_fill() {
+    let (root, leaves) = two_part_tree();
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let immutables = Immutables {
+        extension: Some(extension(EXTENSION_FLAG_MERKLE, &[root, u64_to_u256(2)])),
+        ..immutables(1, 1_000, 100)
+    };
+    let escrow_id = create(immutables).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    mock::advance(100);
+    mock::run(withdraw_partial(escrow_id.clone(), secret(1), vec![leaves[1]], 1)).unwrap();
+
+    // `secret(1)` also opens the ordinary hashlock, but the remainder is only reachable by fills
+    assert_eq!(mock::run(withdraw_with_secret(escrow_id.clone(), secret(1))).err(), Some(EscrowError::InvalidInput));
+    mock::advance(100);
+    assert_eq!(mock::run(public_withdraw_with_secret(escrow_id.clone(), secret(1))).err(), Some(EscrowError::InvalidInput));
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().filled_amount, 500);
+    assert!(!escrow(&escrow_id).withdrawn);
+}
@@ -0,0 +1,97 @@
+use super::*;
+
+/// Funded native-ICP escrow for order 1 whose withdrawal window is open, monitored from
+/// `lookback` blocks below the head
+fn withdrawable_escrow(lookback: Option<u64>) -> String {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = insert_escrow(
+        immutables(1, 1_000, 100), principal(1), None, None, EVM_CHAIN, EVM_ESCROW.to_string(), None, lookback,
+    ).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    mock::advance(100);
+    escrow_id
+}
+
+fn verified_withdrawal(escrow_id: &str) -> Result<WithdrawalReceipt, EscrowError> {
+    mock::run(withdraw_with_evm_verification(escrow_id.to_string(), secret(1)))
+}
+
+#[test]
+fn pays_out_once_the_reveal_is_buried_under_the_confirmations() {
+    let escrow_id = withdrawable_escrow(None);
+    // Ethereum needs 12 confirmations by default
+    serve_evm_chain(1_011, vec![reveal_log(1, 1_000)]);
+    assert_eq!(verified_withdrawal(&escrow_id).unwrap_err(), EscrowError::RevealNotConfirmed);
+    assert!(!escrow(&escrow_id).withdrawn);
+
+    serve_evm_chain(1_012, vec![reveal_log(1, 1_000)]);
+    verified_withdrawal(&escrow_id).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+    assert!(escrow(&escrow_id).reveal_confirmed_at.is_some());
+}
+
+#[test]
+fn missing_reveal_is_not_confirmed() {
+    let escrow_id = withdrawable_escrow(None);
+    serve_evm_chain(5_000, vec![]);
+    assert_eq!(verified_withdrawal(&escrow_id).unwrap_err(), EscrowError::RevealNotConfirmed);
+}
+
+#[test]
+fn searches_from_the_block_monitoring_saw_the_reveal_in() {
+    let escrow_id = withdrawable_escrow(Some(1_000));
+    serve_evm_chain(1_000, vec![reveal_log(1, 100)]);
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id.clone()));
+    assert!(matches!(outcome, Ok(MonitorOutcome::SecretFound(found)) if found == secret(1)));
+    assert_eq!(escrow(&escrow_id).reveal_block, Some(100));
+
+    // Far more than REVEAL_LOOKBACK_BLOCKS later
+    serve_evm_chain(100_000, vec![reveal_log(1, 100)]);
+    verified_withdrawal(&escrow_id).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+}
+
+#[test]
+fn reveal_search_starts_after_what_monitoring_already_searched() {
+    let mut escrow = escrow(&create(immutables(1, 1_000, 100)).unwrap());
+    assert_eq!(reveal_search_start(&escrow, 10_000), 10_000 - REVEAL_LOOKBACK_BLOCKS);
+    escrow.last_scanned_block = 400;
+    assert_eq!(reveal_search_start(&escrow, 10_000), 401);
+    escrow.reveal_block = Some(42);
+    assert_eq!(reveal_search_start(&escrow, 10_000), 42);
+}
+
+#[test]
+fn rpc_failure_is_reported_apart_from_an_unconfirmed_reveal() {
+    let escrow_id = withdrawable_escrow(None);
+    mock::set_rpc(|_, _| RpcResult::Err("HTTP 429 Too Many Requests".to_string()));
+
+    let error = verified_withdrawal(&escrow_id).unwrap_err();
+    assert!(matches!(error, EscrowError::FinalityCheckFailed(_)), "{:?}", error);
+    assert!(!escrow(&escrow_id).withdrawn);
+}
+
+fn manual_withdrawal(escrow_id: &str) -> Result<WithdrawalReceipt, EscrowError> {
+    mock::run(withdraw_with_secret(escrow_id.to_string(), secret(1)))
+}
+
+#[test]
+fn manual_withdrawal_skips_finality_by_default() {
+    let escrow_id = withdrawable_escrow(None);
+    manual_withdrawal(&escrow_id).unwrap();
+    assert_eq!(mock::rpc_calls(), 0);
+}
+
+#[test]
+fn enforced_finality_holds_manual_withdrawals_until_monitoring_confirms_the_reveal() {
+    let escrow_id = withdrawable_escrow(Some(100));
+    set_enforce_finality_on_manual_withdraw(true).unwrap();
+    assert_eq!(manual_withdrawal(&escrow_id).unwrap_err(), EscrowError::RevealNotConfirmed);
+
+    serve_evm_chain(1_012, vec![reveal_log(1, 1_000)]);
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id.clone()));
+    assert!(matches!(outcome, Ok(MonitorOutcome::SecretFound(_))));
+    manual_withdrawal(&escrow_id).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+}
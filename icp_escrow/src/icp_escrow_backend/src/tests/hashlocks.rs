@@ -0,0 +1,143 @@
+use super::*;
+
+fn hex32(hex: &str) -> [u8; 32] {
+    hex::decode(hex).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn compute_hashlock_matches_known_vectors() {
+    let vectors = [
+        (HashAlgo::Keccak256, "", "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"),
+        (HashAlgo::Keccak256, "abc", "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"),
+        (HashAlgo::Sha3_256, "", "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"),
+        (HashAlgo::Sha3_256, "abc", "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"),
+        (HashAlgo::Sha256, "", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+        (HashAlgo::Sha256, "abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+    ];
+    for (algo, secret, expected) in vectors {
+        assert_eq!(compute_hashlock(secret.as_bytes().to_vec(), algo), hex32(expected), "{:?}({:?})", algo, secret);
+    }
+}
+
+#[test]
+fn binding_the_order_hash_changes_the_hashlock() {
+    let order_hash = [9; 32];
+    let unbound = compute_bound_hashlock(secret(1), order_hash, HashlockBinding::SecretOnly);
+    let bound = compute_bound_hashlock(secret(1), order_hash, HashlockBinding::SecretAndOrderHash);
+
+    assert_eq!(unbound, keccak256(&secret(1)));
+    assert_eq!(bound, keccak256(&[secret(1), order_hash].concat()));
+    assert_ne!(bound, compute_bound_hashlock(secret(1), [8; 32], HashlockBinding::SecretAndOrderHash));
+
+    let verify = |hashlock: &[u8; 32], binding| verify_hashlock(&secret(1), hashlock, HashAlgo::Keccak256, binding, &order_hash);
+    assert!(verify(&unbound, HashlockBinding::SecretOnly));
+    assert!(!verify(&unbound, HashlockBinding::SecretAndOrderHash));
+    assert!(verify(&bound, HashlockBinding::SecretAndOrderHash));
+    assert!(!verify(&bound, HashlockBinding::SecretOnly));
+}
+
+#[test]
+fn bound_escrow_rejects_the_bare_secret_hashlock() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    update_escrow(&escrow_id, |escrow| escrow.hashlock_binding = HashlockBinding::SecretAndOrderHash);
+    mock::advance(100);
+
+    let withdrawal = mock::run(withdraw_with_secret(escrow_id.clone(), secret(1)));
+    assert_eq!(withdrawal.err(), Some(EscrowError::InvalidSecret));
+    assert!(!escrow(&escrow_id).withdrawn);
+}
+
+/// Withdraw an escrow for order 1 and fund a second order locked to the same secret; returns
+/// the second escrow, ready to withdraw
+fn escrow_reusing_a_withdrawn_secret() -> String {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let first = create(immutables(1, 1_000, 100)).unwrap();
+    let second = create(Immutables { order_hash: [2; 32], ..immutables(1, 1_000, 100) }).unwrap();
+    deposit(&first, payer).unwrap();
+    deposit(&second, payer).unwrap();
+    mock::advance(100);
+    mock::run(withdraw_with_secret(first, secret(1))).unwrap();
+    second
+}
+
+#[test]
+fn reused_secret_is_rejected_under_the_reject_policy() {
+    let escrow_id = escrow_reusing_a_withdrawn_secret();
+    set_duplicate_secret_policy(DuplicateSecretPolicy::Reject).unwrap();
+
+    let withdrawal = mock::run(withdraw_with_secret(escrow_id.clone(), secret(1)));
+    assert_eq!(withdrawal.err(), Some(EscrowError::SecretReused));
+    assert!(!escrow(&escrow_id).withdrawn);
+}
+
+#[test]
+fn reused_secret_withdraws_under_the_default_warn_policy() {
+    let escrow_id = escrow_reusing_a_withdrawn_secret();
+    assert_eq!(CONFIG.with(|config| config.borrow().duplicate_secret_policy), DuplicateSecretPolicy::Warn);
+
+    mock::run(withdraw_with_secret(escrow_id.clone(), secret(1))).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+}
+
+#[test]
+fn reused_secret_withdraws_under_the_allow_policy() {
+    let escrow_id = escrow_reusing_a_withdrawn_secret();
+    set_duplicate_secret_policy(DuplicateSecretPolicy::Allow).unwrap();
+
+    mock::run(withdraw_with_secret(escrow_id.clone(), secret(1))).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+}
+
+#[test]
+fn hex32_inputs_normalize_with_or_without_the_prefix() {
+    let bytes = [0xab; 32];
+    let digits = hex::encode(bytes);
+    assert_eq!(normalize_hex32(&digits), Ok(bytes));
+    assert_eq!(normalize_hex32(&format!("0x{}", digits)), Ok(bytes));
+    assert_eq!(normalize_hex32(&format!("0X{}", digits.to_uppercase())), Ok(bytes));
+    assert_eq!(bytes32_to_hex(&bytes), format!("0x{}", digits));
+}
+
+#[test]
+fn malformed_hex32_inputs_fail_with_invalid_hex() {
+    let digits = hex::encode([0xab; 32]);
+    let malformed = [
+        String::new(),
+        "0x".to_string(),
+        digits[..62].to_string(),              // 31 bytes
+        format!("{}ab", digits),               // 33 bytes
+        digits[..63].to_string(),              // odd length
+        format!("{}zz", &digits[..62]),        // non-hex
+        format!("0x0x{}", &digits[..60]),
+    ];
+    for input in malformed {
+        assert!(matches!(normalize_hex32(&input), Err(EscrowError::InvalidHex(_))), "{:?}", input);
+    }
+
+    // Public endpoints surface the same error
+    let withdrawal = mock::run(withdraw_with_hex_secret("escrow_1".to_string(), digits[..63].to_string()));
+    assert!(matches!(withdrawal, Err(EscrowError::InvalidHex(_))));
+}
+
+#[test]
+fn cross_chain_secrets_round_trip_in_the_declared_format() {
+    let secret = [0x5c; 32];
+    let digits = hex::encode(secret);
+    assert_eq!(process_cross_chain_secret(&format!("0x{}", digits), SecretFormat::Hex), Ok(secret));
+    assert_eq!(process_cross_chain_secret(&digits, SecretFormat::Hex), Ok(secret));
+
+    let raw = "correct horse battery staple !!!";
+    assert_eq!(process_cross_chain_secret(raw, SecretFormat::RawUtf8).map(|b| b.to_vec()), Ok(raw.as_bytes().to_vec()));
+}
+
+#[test]
+fn truncated_hex_is_never_taken_as_raw_bytes() {
+    let digits = hex::encode([0x5c; 32]);
+    assert_eq!(process_cross_chain_secret(&digits[..32], SecretFormat::RawUtf8), Err(EscrowError::InvalidInput));
+    assert!(matches!(process_cross_chain_secret(&digits[..32], SecretFormat::Hex), Err(EscrowError::InvalidHex(_))));
+    assert_eq!(process_cross_chain_secret("too short", SecretFormat::RawUtf8), Err(EscrowError::InvalidInput));
+}
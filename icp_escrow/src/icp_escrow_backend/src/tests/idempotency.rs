@@ -0,0 +1,50 @@
+use super::*;
+
+fn create_with_key(immutables: Immutables, key: &str) -> Result<String, EscrowError> {
+    mock::run(create_escrow_idempotent(
+        immutables, principal(1), None, None, EVM_CHAIN, EVM_ESCROW.to_string(), None, None, key.to_string(),
+    ))
+}
+
+fn escrow_count() -> usize {
+    ESCROWS.with(|escrows| escrows.borrow().len())
+}
+
+#[test]
+fn repeated_key_returns_the_original_escrow() {
+    let first = create_with_key(immutables(1, 1_000, 100), "retry-1").unwrap();
+    assert_eq!(create_with_key(immutables(1, 1_000, 100), "retry-1").unwrap(), first);
+    assert_eq!(escrow_count(), 1);
+}
+
+#[test]
+fn different_key_creates_a_new_escrow() {
+    let first = create_with_key(immutables(1, 1_000, 100), "retry-1").unwrap();
+    let second = create_with_key(immutables(1, 1_000, 100), "retry-2").unwrap();
+    assert_ne!(first, second);
+    assert_eq!(escrow_count(), 2);
+}
+
+#[test]
+fn keys_are_scoped_per_caller() {
+    mock::set_caller(principal(20));
+    let first = create_with_key(immutables(1, 1_000, 100), "shared").unwrap();
+    mock::set_caller(principal(21));
+    let second = create_with_key(immutables(1, 1_000, 100), "shared").unwrap();
+
+    assert_ne!(first, second);
+    assert_eq!(escrow(&second).icp_taker, principal(21));
+}
+
+#[test]
+fn reusing_a_key_with_other_arguments_is_rejected() {
+    create_with_key(immutables(1, 1_000, 100), "retry-1").unwrap();
+
+    assert_eq!(create_with_key(immutables(1, 2_000, 100), "retry-1"), Err(EscrowError::IdempotencyKeyReused));
+    assert_eq!(escrow_count(), 1);
+}
+
+#[test]
+fn empty_key_is_rejected() {
+    assert_eq!(create_with_key(immutables(1, 1_000, 100), ""), Err(EscrowError::InvalidInput));
+}
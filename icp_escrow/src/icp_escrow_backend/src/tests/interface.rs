@@ -0,0 +1,12 @@
+use super::*;
+
+#[test]
+fn did_file_matches_the_exported_interface() {
+    let exported = __export_service();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/icp_escrow_backend.did");
+    if std::env::var_os("UPDATE_CANDID").is_some() {
+        std::fs::write(path, &exported).unwrap();
+    }
+    let did = std::fs::read_to_string(path).unwrap();
+    assert!(did == exported, "icp_escrow_backend.did is stale; rerun the tests with UPDATE_CANDID=1");
+}
@@ -0,0 +1,38 @@
+use super::*;
+
+fn create_on_chain(seed: u8, chain_id: u64) -> String {
+    insert_escrow(immutables(seed, 1_000, 100), principal(1), None, None, chain_id, EVM_ESCROW.to_string(), None, None).unwrap()
+}
+
+#[test]
+fn chain_filter_selects_one_chain_in_creation_order() {
+    let first = create_on_chain(1, 1);
+    let polygon = create_on_chain(2, 137);
+    let second = create_on_chain(3, 1);
+    create_on_chain(4, 10);
+    let third = create_on_chain(5, 1);
+
+    let ids = |chain_id, offset, limit| -> Vec<String> {
+        get_escrows_by_chain(chain_id, offset, limit).into_iter().map(|(id, _)| id).collect()
+    };
+    assert_eq!(ids(1, 0, 10), vec![first, second.clone(), third.clone()]);
+    assert_eq!(ids(1, 1, 1), vec![second]);
+    assert_eq!(ids(1, 2, 10), vec![third]);
+    assert_eq!(ids(137, 0, 10), vec![polygon]);
+    assert!(ids(56, 0, 10).is_empty());
+
+    let (_, summary) = &get_escrows_by_chain(137, 0, 1)[0];
+    assert_eq!((summary.evm_chain_id, summary.amount), (137, 1_000));
+}
+
+#[test]
+fn listings_come_back_in_the_same_creation_order_every_call() {
+    let created: Vec<String> = (1..=12).map(|seed| create(immutables(seed, 1_000, 100)).unwrap()).collect();
+    let ids = |listing: Vec<(String, EscrowState)>| -> Vec<String> { listing.into_iter().map(|(id, _)| id).collect() };
+
+    let first = ids(list_all_escrows());
+    assert_eq!(first, ids(list_all_escrows()));
+    // Numeric, not lexicographic: escrow_10 follows escrow_9
+    assert_eq!(first, created);
+    assert_eq!(ids(list_escrows(0, 100, EscrowFilter::default()).unwrap()), created);
+}
@@ -0,0 +1,383 @@
+//! In-memory stand-in for the `host` interface: a settable clock and caller, timers that fire
+//! when the test advances time, ICRC-1/ICRC-2 ledgers, a scriptable EVM RPC canister, and
+//! threshold ECDSA backed by `ecdsa_key`.
+//! Every `#[test]` runs on its own thread, so this state (like the canister's) starts fresh.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::time::Duration;
+
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use candid::{decode_args, encode_args, Nat, Principal};
+use ic_cdk::api::call::{CallResult, RejectionCode};
+use ic_cdk::api::management_canister::ecdsa::{
+    EcdsaPublicKeyArgument, EcdsaPublicKeyResponse, SignWithEcdsaArgument, SignWithEcdsaResponse,
+};
+use k256::ecdsa::signature::hazmat::PrehashSigner;
+use k256::ecdsa::{Signature, SigningKey};
+use icrc_ledger_types::icrc1::account::Account;
+use icrc_ledger_types::icrc1::transfer::{TransferArg, TransferError};
+use icrc_ledger_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
+
+use crate::{RpcResult, RpcSource};
+
+/// Principal the mocked canister runs as
+pub const CANISTER_ID: &str = "bkyz2-fmaaa-aaaaa-qaaaq-cai";
+/// Controller every mocked canister starts with
+pub const CONTROLLER: &str = "aaaaa-aa";
+/// Clock at the start of every test: 2023-11-14T22:13:20Z
+pub const GENESIS_SECS: u64 = 1_700_000_000;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+type RpcHandler = Box<dyn FnMut(&RpcSource, &serde_json::Value) -> RpcResult<String>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+enum TimerTask {
+    Once(Box<dyn FnOnce()>),
+    Interval(Box<dyn FnMut()>, u64),
+}
+
+/// A mocked ICRC-1/ICRC-2 ledger. Allowances aren't modelled: `icrc2_transfer_from` only
+/// needs the payer's balance.
+pub struct MockLedger {
+    pub fee: u64,
+    balances: HashMap<(Principal, [u8; 32]), u128>,
+    /// Method whose calls the ledger rejects, e.g. to fail one leg of a deposit
+    pub failing_method: Option<&'static str>,
+    next_block: u64,
+}
+
+impl MockLedger {
+    fn new(fee: u64) -> Self {
+        Self { fee, balances: HashMap::new(), failing_method: None, next_block: 0 }
+    }
+
+    fn key(account: &Account) -> (Principal, [u8; 32]) {
+        (account.owner, account.subaccount.unwrap_or([0u8; 32]))
+    }
+
+    fn balance(&self, account: &Account) -> u128 {
+        self.balances.get(&Self::key(account)).copied().unwrap_or(0)
+    }
+
+    /// Move `amount` plus the fee out of `from`, returning the payer's balance on shortfall
+    fn settle(&mut self, from: &Account, to: &Account, amount: u128) -> Result<u64, u128> {
+        let balance = self.balance(from);
+        let debit = amount + self.fee as u128;
+        if balance < debit {
+            return Err(balance);
+        }
+        self.balances.insert(Self::key(from), balance - debit);
+        *self.balances.entry(Self::key(to)).or_default() += amount;
+        self.next_block += 1;
+        Ok(self.next_block)
+    }
+
+    fn handle(&mut self, method: &str, args: &[u8]) -> CallResult<Vec<u8>> {
+        if self.failing_method == Some(method) {
+            return Err((RejectionCode::SysTransient, format!("{} unavailable", method)));
+        }
+        let encoded = match method {
+            "icrc1_fee" => encode_args((Nat::from(self.fee),)),
+            "icrc1_balance_of" => {
+                let (account,): (Account,) = decode(args)?;
+                encode_args((Nat::from(self.balance(&account)),))
+            }
+            "icrc1_transfer" => {
+                let (arg,): (TransferArg,) = decode(args)?;
+                let result = if arg.fee.as_ref().is_some_and(|fee| *fee != self.fee) {
+                    Err(TransferError::BadFee { expected_fee: Nat::from(self.fee) })
+                } else {
+                    let from = Account { owner: id(), subaccount: arg.from_subaccount };
+                    self.settle(&from, &arg.to, nat_to_u128(&arg.amount))
+                        .map(Nat::from)
+                        .map_err(|balance| TransferError::InsufficientFunds { balance: Nat::from(balance) })
+                };
+                encode_args((result,))
+            }
+            "icrc2_transfer_from" => {
+                let (arg,): (TransferFromArgs,) = decode(args)?;
+                let result = self.settle(&arg.from, &arg.to, nat_to_u128(&arg.amount))
+                    .map(Nat::from)
+                    .map_err(|balance| TransferFromError::InsufficientFunds { balance: Nat::from(balance) });
+                encode_args((result,))
+            }
+            other => return Err((RejectionCode::CanisterError, format!("ledger has no method {}", other))),
+        };
+        Ok(encoded.expect("encodable ledger response"))
+    }
+}
+
+struct MockHost {
+    now_nanos: u64,
+    caller: Principal,
+    controllers: Vec<Principal>,
+    cycles: u128,
+    ledgers: HashMap<Principal, MockLedger>,
+    rpc: Option<RpcHandler>,
+    rpc_calls: usize,
+    rpc_response_limits: Vec<(String, u64)>,
+    notifications: Vec<(Principal, String)>,
+    ecdsa_calls: usize,
+    timers: BTreeMap<u64, (u64, TimerTask)>,
+    next_timer: u64,
+}
+
+thread_local! {
+    static HOST: RefCell<MockHost> = RefCell::new(MockHost {
+        now_nanos: GENESIS_SECS * NANOS_PER_SEC,
+        caller: Principal::from_text(CONTROLLER).unwrap(),
+        controllers: vec![Principal::from_text(CONTROLLER).unwrap()],
+        cycles: 10_000_000_000_000,
+        ledgers: HashMap::new(),
+        rpc: None,
+        rpc_calls: 0,
+        rpc_response_limits: Vec::new(),
+        notifications: Vec::new(),
+        ecdsa_calls: 0,
+        timers: BTreeMap::new(),
+        next_timer: 0,
+    });
+}
+
+fn decode<'a, T: ArgumentDecoder<'a>>(args: &'a [u8]) -> CallResult<T> {
+    decode_args(args).map_err(|e| (RejectionCode::CanisterError, format!("bad arguments: {}", e)))
+}
+
+fn nat_to_u128(value: &Nat) -> u128 {
+    u128::try_from(&value.0).expect("amount fits in u128")
+}
+
+// -----------------------------------------------------------------------------
+// The `host` interface
+// -----------------------------------------------------------------------------
+
+pub fn time() -> u64 {
+    HOST.with(|host| host.borrow().now_nanos)
+}
+
+pub fn caller() -> Principal {
+    HOST.with(|host| host.borrow().caller)
+}
+
+pub fn id() -> Principal {
+    Principal::from_text(CANISTER_ID).unwrap()
+}
+
+pub fn is_controller(principal: &Principal) -> bool {
+    HOST.with(|host| host.borrow().controllers.contains(principal))
+}
+
+pub fn canister_balance128() -> u128 {
+    HOST.with(|host| host.borrow().cycles)
+}
+
+pub fn print<S: AsRef<str>>(_message: S) {}
+
+pub async fn call<T: ArgumentEncoder, R: for<'a> ArgumentDecoder<'a>>(
+    id: Principal,
+    method: &str,
+    args: T,
+) -> CallResult<R> {
+    let args = encode_args(args).expect("encodable arguments");
+    let response = HOST.with(|host| match host.borrow_mut().ledgers.get_mut(&id) {
+        Some(ledger) => ledger.handle(method, &args),
+        None => Err((RejectionCode::DestinationInvalid, format!("no canister {}", id))),
+    })?;
+    decode(&response)
+}
+
+pub async fn call_with_payment128<T: ArgumentEncoder, R: for<'a> ArgumentDecoder<'a>>(
+    _id: Principal,
+    method: &str,
+    args: T,
+    _cycles: u128,
+) -> CallResult<R> {
+    assert_eq!(method, "request", "only EVM RPC requests are paid calls");
+    let args = encode_args(args).expect("encodable arguments");
+    let (source, request, max_response_bytes): (RpcSource, String, u64) = decode(&args)?;
+    let request: serde_json::Value = serde_json::from_str(&request).expect("JSON-RPC request");
+    // Take the handler out so it can't observe a borrowed host
+    let handler = HOST.with(|host| {
+        let mut host = host.borrow_mut();
+        host.rpc_calls += 1;
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        host.rpc_response_limits.push((method, max_response_bytes));
+        host.rpc.take()
+    });
+    let Some(mut handler) = handler else {
+        return Err((RejectionCode::DestinationInvalid, "no EVM RPC canister".to_string()));
+    };
+    let result = handler(&source, &request);
+    HOST.with(|host| host.borrow_mut().rpc = Some(handler));
+    decode(&encode_args((result,)).expect("encodable RPC response"))
+}
+
+pub fn notify<T: ArgumentEncoder>(id: Principal, method: &str, _args: T) -> Result<(), RejectionCode> {
+    HOST.with(|host| host.borrow_mut().notifications.push((id, method.to_string())));
+    Ok(())
+}
+
+pub async fn ecdsa_public_key(_arg: EcdsaPublicKeyArgument) -> CallResult<(EcdsaPublicKeyResponse,)> {
+    HOST.with(|host| host.borrow_mut().ecdsa_calls += 1);
+    let public_key = ecdsa_key().verifying_key().to_encoded_point(true).as_bytes().to_vec();
+    Ok((EcdsaPublicKeyResponse { public_key, chain_code: vec![] },))
+}
+
+pub async fn sign_with_ecdsa(arg: SignWithEcdsaArgument) -> CallResult<(SignWithEcdsaResponse,)> {
+    HOST.with(|host| host.borrow_mut().ecdsa_calls += 1);
+    let signature: Signature = ecdsa_key().sign_prehash(&arg.message_hash)
+        .map_err(|e| (RejectionCode::CanisterError, e.to_string()))?;
+    Ok((SignWithEcdsaResponse { signature: signature.to_bytes().to_vec() },))
+}
+
+/// Runs the future to completion right away; only timers spawn, and they fire outside any executor
+pub fn spawn<F: 'static + Future<Output = ()>>(future: F) {
+    futures::executor::block_on(future);
+}
+
+fn schedule(delay: Duration, task: TimerTask) -> TimerId {
+    HOST.with(|host| {
+        let mut host = host.borrow_mut();
+        host.next_timer += 1;
+        let id = host.next_timer;
+        let due = host.now_nanos + delay.as_nanos() as u64;
+        host.timers.insert(id, (due, task));
+        TimerId(id)
+    })
+}
+
+pub fn set_timer(delay: Duration, func: impl FnOnce() + 'static) -> TimerId {
+    schedule(delay, TimerTask::Once(Box::new(func)))
+}
+
+pub fn set_timer_interval(interval: Duration, func: impl FnMut() + 'static) -> TimerId {
+    schedule(interval, TimerTask::Interval(Box::new(func), interval.as_nanos() as u64))
+}
+
+pub fn clear_timer(id: TimerId) {
+    HOST.with(|host| host.borrow_mut().timers.remove(&id.0));
+}
+
+// -----------------------------------------------------------------------------
+// Test controls
+// -----------------------------------------------------------------------------
+
+/// Run `future` (an endpoint call) to completion
+pub fn run<F: Future>(future: F) -> F::Output {
+    futures::executor::block_on(future)
+}
+
+pub fn set_caller(principal: Principal) {
+    HOST.with(|host| host.borrow_mut().caller = principal);
+}
+
+pub fn controller() -> Principal {
+    Principal::from_text(CONTROLLER).unwrap()
+}
+
+pub fn set_cycles(cycles: u128) {
+    HOST.with(|host| host.borrow_mut().cycles = cycles);
+}
+
+/// Move the clock forward, firing every timer that falls due on the way
+pub fn advance(secs: u64) {
+    let target = time() + secs * NANOS_PER_SEC;
+    loop {
+        let task = HOST.with(|host| {
+            let mut host = host.borrow_mut();
+            let (id, due) = host.timers.iter()
+                .filter(|(_, (due, _))| *due <= target)
+                .min_by_key(|(id, (due, _))| (*due, **id))
+                .map(|(id, (due, _))| (*id, *due))?;
+            host.now_nanos = host.now_nanos.max(due);
+            let (_, task) = host.timers.remove(&id)?;
+            if let TimerTask::Interval(_, interval) = &task {
+                // Keep the slot so the callback can clear its own timer
+                host.timers.insert(id, (due + interval, TimerTask::Once(Box::new(|| {}))));
+            }
+            Some((id, task))
+        });
+        match task {
+            None => break,
+            Some((_, TimerTask::Once(func))) => func(),
+            Some((id, TimerTask::Interval(mut func, interval))) => {
+                func();
+                HOST.with(|host| {
+                    if let Some((_, task)) = host.borrow_mut().timers.get_mut(&id) {
+                        *task = TimerTask::Interval(func, interval);
+                    }
+                });
+            }
+        }
+    }
+    HOST.with(|host| host.borrow_mut().now_nanos = target);
+}
+
+/// Number of timers still scheduled
+pub fn pending_timers() -> usize {
+    HOST.with(|host| host.borrow().timers.len())
+}
+
+/// Register a ledger canister charging `fee` per transfer
+pub fn install_ledger(ledger: Principal, fee: u64) {
+    HOST.with(|host| host.borrow_mut().ledgers.insert(ledger, MockLedger::new(fee)));
+}
+
+/// Credit `amount` to `account` out of thin air
+pub fn mint(ledger: Principal, account: Account, amount: u128) {
+    with_ledger(ledger, |l| *l.balances.entry(MockLedger::key(&account)).or_default() += amount);
+}
+
+pub fn balance(ledger: Principal, account: Account) -> u128 {
+    with_ledger(ledger, |l| l.balance(&account))
+}
+
+/// Make the ledger reject calls to `method` (None to heal it)
+pub fn fail_ledger_method(ledger: Principal, method: Option<&'static str>) {
+    with_ledger(ledger, |l| l.failing_method = method);
+}
+
+pub fn with_ledger<T>(ledger: Principal, f: impl FnOnce(&mut MockLedger) -> T) -> T {
+    HOST.with(|host| f(host.borrow_mut().ledgers.get_mut(&ledger).expect("ledger installed")))
+}
+
+/// Answer EVM RPC requests with `handler`
+pub fn set_rpc(handler: impl FnMut(&RpcSource, &serde_json::Value) -> RpcResult<String> + 'static) {
+    HOST.with(|host| host.borrow_mut().rpc = Some(Box::new(handler)));
+}
+
+/// EVM RPC requests made so far
+pub fn rpc_calls() -> usize {
+    HOST.with(|host| host.borrow().rpc_calls)
+}
+
+/// (canister, method) of every notification sent so far
+/// Max-response-bytes each RPC request asking `method` was sent with, in call order
+pub fn rpc_response_limits(method: &str) -> Vec<u64> {
+    HOST.with(|host| {
+        host.borrow().rpc_response_limits.iter()
+            .filter(|(called, _)| called == method)
+            .map(|(_, limit)| *limit)
+            .collect()
+    })
+}
+
+pub fn notifications() -> Vec<(Principal, String)> {
+    HOST.with(|host| host.borrow().notifications.clone())
+}
+
+
+/// The canister's threshold-ECDSA key
+pub fn ecdsa_key() -> SigningKey {
+    SigningKey::from_slice(&[0x17; 32]).expect("valid secp256k1 scalar")
+}
+
+/// Calls made to the management canister's threshold-ECDSA methods
+pub fn ecdsa_calls() -> usize {
+    HOST.with(|host| host.borrow().ecdsa_calls)
+}
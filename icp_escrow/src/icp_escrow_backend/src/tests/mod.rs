@@ -0,0 +1,179 @@
+//! Unit tests. Canister state lives in thread-locals and every test runs on its own thread,
+//! so each test starts from a fresh canister.
+
+mod accounting;
+mod archive;
+mod attestations;
+mod audit;
+mod auction;
+mod auto_monitor;
+mod callbacks;
+mod cancellation;
+mod chains;
+mod compact;
+mod consensus;
+mod creation;
+mod cross_chain;
+mod decimals;
+mod deposits;
+mod event_signatures;
+mod extensions;
+mod finality;
+mod hashlocks;
+mod idempotency;
+mod monitoring;
+mod interface;
+mod listings;
+pub mod mock;
+mod partial_fills;
+mod rate_limits;
+mod response_limits;
+mod self_test;
+mod receipts;
+mod reveal_order;
+mod signed_orders;
+mod timelocks;
+mod token_ledger;
+mod withdrawal_failures;
+
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc1::account::Account;
+
+use crate::*;
+
+/// Distinct non-anonymous principal per `n`
+pub fn principal(n: u8) -> Principal {
+    Principal::from_slice(&[n; 10])
+}
+
+pub fn icp_ledger() -> Principal {
+    get_icp_ledger_principal()
+}
+
+pub fn secret(n: u8) -> [u8; 32] {
+    [n; 32]
+}
+
+/// EVM address word (20 bytes, left-padded) with every byte `n`
+pub fn evm_address(n: u8) -> Address {
+    let mut address = [0u8; 32];
+    address[12..].copy_from_slice(&[n; 20]);
+    address
+}
+
+pub const EVM_ESCROW: &str = "0x1111111111111111111111111111111111111111";
+pub const EVM_CHAIN: u64 = 1;
+
+/// Native-ICP order `seed` locking `amount` with `safety_deposit`, hashlocked to `secret(seed)`.
+/// Dst withdrawal opens after 100s, public withdrawal after 200s, cancellation after 300s.
+pub fn immutables(seed: u8, amount: u64, safety_deposit: u64) -> Immutables {
+    Immutables {
+        order_hash: [seed; 32],
+        hashlock: keccak256(&secret(seed)),
+        maker: evm_address(0xaa),
+        taker: evm_address(0xbb),
+        token: [0u8; 32],
+        amount: u64_to_u256(amount),
+        safety_deposit: u64_to_u256(safety_deposit),
+        timelocks: Timelocks::new(10, 20, 30, 40, 100, 200, 300, 0),
+        extension: None,
+        hash_algo: None,
+    }
+}
+
+/// Create a native-ICP destination escrow as the current caller
+pub fn create(immutables: Immutables) -> Result<String, EscrowError> {
+    insert_escrow(immutables, principal(1), None, None, EVM_CHAIN, EVM_ESCROW.to_string(), None, None)
+}
+
+pub fn escrow(escrow_id: &str) -> EscrowState {
+    ESCROWS.with(|escrows| escrows.borrow().get(escrow_id).cloned()).expect("escrow exists")
+}
+
+pub fn update_escrow(escrow_id: &str, f: impl FnOnce(&mut EscrowState)) {
+    ESCROWS.with(|escrows| f(escrows.borrow_mut().get_mut(escrow_id).expect("escrow exists")));
+}
+
+pub fn deposit_account(escrow_id: &str) -> Account {
+    escrow_deposit_account(escrow_id, &escrow(escrow_id)).unwrap()
+}
+
+/// Install the ICP ledger (fee 10) and fund `payer` with plenty of ICP
+pub fn with_icp_ledger(payer: Principal) {
+    mock::install_ledger(icp_ledger(), 10);
+    mock::mint(icp_ledger(), Account::from(payer), 1_000_000_000);
+}
+
+/// Map a fresh EVM token to `ledger` (installed with fee 10) and return the token address
+pub fn register_token(ledger: Principal) -> Address {
+    mock::install_ledger(ledger, 10);
+    let token = evm_address(0x70);
+    register_token_mapping(format!("0x{}", hex::encode(&token[12..])), ledger).unwrap();
+    token
+}
+
+/// Fund an escrow through `deposit_to_escrow` as `payer`
+pub fn deposit(escrow_id: &str, payer: Principal) -> Result<Nat, EscrowError> {
+    mock::set_caller(payer);
+    let result = mock::run(deposit_to_escrow(escrow_id.to_string()));
+    mock::set_caller(mock::controller());
+    result
+}
+
+/// Merkle root and proofs of a two-part secret tree over `secret(1)` and `secret(2)`
+pub fn two_part_tree() -> ([u8; 32], [[u8; 32]; 2]) {
+    let leaves = [partial_fill_leaf(1, &secret(1)), partial_fill_leaf(2, &secret(2))];
+    let (a, b) = if leaves[0] <= leaves[1] { (leaves[0], leaves[1]) } else { (leaves[1], leaves[0]) };
+    (keccak256(&[a, b].concat()), leaves)
+}
+
+/// Serve an EVM chain at block `head` through the mocked RPC canister. eth_getLogs returns the
+/// `logs` inside the requested block range; transactions are unknown.
+pub fn serve_evm_chain(head: u64, logs: Vec<serde_json::Value>) {
+    mock::set_rpc(evm_chain(head, logs));
+}
+
+/// RPC handler answering like `serve_evm_chain`, for handlers that only serve some providers
+pub fn evm_chain(head: u64, logs: Vec<serde_json::Value>) -> impl FnMut(&RpcSource, &serde_json::Value) -> RpcResult<String> {
+    move |_, request| {
+        let result = match request["method"].as_str() {
+            Some("eth_blockNumber") => serde_json::json!(format!("0x{:x}", head)),
+            Some("eth_getLogs") => {
+                let filter = &request["params"][0];
+                let block = |value: &serde_json::Value| parse_quantity("block", value.as_str()).unwrap();
+                let range = block(&filter["fromBlock"])..=block(&filter["toBlock"]);
+                logs.iter().filter(|log| range.contains(&block(&log["blockNumber"]))).cloned().collect()
+            }
+            _ => serde_json::Value::Null,
+        };
+        RpcResult::Ok(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string())
+    }
+}
+
+/// SecretRevealed log of order `seed` (revealing `secret(seed)`) mined in `block`
+pub fn reveal_log(seed: u8, block: u64) -> serde_json::Value {
+    serde_json::json!({
+        "address": EVM_ESCROW,
+        "topics": [
+            format!("0x{}", hex::encode(keccak256(SECRET_REVEALED_EVENT_SIGNATURE.as_bytes()))),
+            format!("0x{}", hex::encode([seed; 32])),
+            format!("0x{}", hex::encode(secret(seed))),
+        ],
+        "data": "0x",
+        "blockNumber": format!("0x{:x}", block),
+        "transactionHash": format!("0x{}", hex::encode([seed; 32])),
+        "logIndex": "0x0",
+    })
+}
+
+#[test]
+fn mock_ledger_moves_funds_into_the_deposit_subaccount() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+
+    deposit(&escrow_id, payer).unwrap();
+
+    assert!(escrow(&escrow_id).funded);
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 1_100);
+}
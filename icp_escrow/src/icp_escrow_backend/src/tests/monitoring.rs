@@ -0,0 +1,180 @@
+use super::*;
+
+/// Escrow for order 1 with auto-withdraw enabled, already paid out
+fn withdrawn_escrow() -> String {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::run(set_auto_withdraw(escrow_id.clone(), true)).unwrap();
+    update_escrow(&escrow_id, |escrow| escrow.withdrawn = true);
+    serve_evm_chain(1_000, vec![reveal_log(1, 900)]);
+    escrow_id
+}
+
+#[test]
+fn completed_escrow_is_a_terminal_outcome_without_rpc_calls() {
+    let escrow_id = withdrawn_escrow();
+
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id));
+    assert!(matches!(outcome, Ok(MonitorOutcome::AlreadyCompleted)));
+    assert_eq!(mock::rpc_calls(), 0);
+    let metrics = get_metrics();
+    assert_eq!((metrics.monitor_calls, metrics.monitor_errors), (1, 0));
+}
+
+#[test]
+fn sweep_skips_completed_escrows_before_any_rpc_call() {
+    let escrow_id = withdrawn_escrow();
+    update_escrow(&escrow_id, |escrow| {
+        escrow.withdrawn = false;
+        escrow.cancelled = true;
+    });
+
+    let results = mock::run(auto_withdraw_sweep()).unwrap();
+    assert!(results.is_empty());
+    assert_eq!(mock::rpc_calls(), 0);
+}
+
+#[test]
+fn metrics_count_matches_empty_scans_and_errors() {
+    let matched = create(immutables(1, 1_000, 100)).unwrap();
+    let empty = create(immutables(2, 1_000, 100)).unwrap();
+    let failed = create(immutables(3, 1_000, 100)).unwrap();
+    let monitor = |escrow_id: &str| mock::run(monitor_evm_secret_revelation(escrow_id.to_string()));
+
+    serve_evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    assert!(matches!(monitor(&matched), Ok(MonitorOutcome::SecretFound(_))));
+    assert!(matches!(monitor(&empty), Ok(MonitorOutcome::NotFound)));
+    mock::set_rpc(|_, _| RpcResult::Err("execution reverted".to_string()));
+    assert!(monitor(&failed).is_err());
+
+    let metrics = get_metrics();
+    assert_eq!(
+        (metrics.monitor_calls, metrics.monitor_matches, metrics.monitor_empty, metrics.monitor_errors),
+        (3, 1, 1, 1),
+    );
+}
+
+#[test]
+fn monitor_calls_within_the_cooldown_are_deferred() {
+    set_min_monitor_interval(30).unwrap();
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::run(set_auto_withdraw(escrow_id.clone(), true)).unwrap();
+    serve_evm_chain(1_000, vec![]);
+    let monitor = || mock::run(monitor_evm_secret_revelation(escrow_id.clone()));
+
+    assert!(matches!(monitor(), Ok(MonitorOutcome::NotFound)));
+    let calls = mock::rpc_calls();
+    mock::advance(10);
+    assert!(matches!(monitor(), Ok(MonitorOutcome::Cooldown { retry_after: 20 })));
+    assert!(mock::run(auto_withdraw_sweep()).unwrap().is_empty());
+    assert_eq!(mock::rpc_calls(), calls);
+
+    mock::advance(20);
+    assert!(matches!(monitor(), Ok(MonitorOutcome::NotFound)));
+    assert!(mock::rpc_calls() > calls);
+}
+
+/// Calldata of `withdraw(secret(seed), immutables)` as sent to the EVM escrow
+fn withdraw_calldata(seed: u8) -> String {
+    let mut calldata = function_selector(WITHDRAW_FUNCTION_SIGNATURES[0]).to_vec();
+    calldata.extend_from_slice(&secret(seed));
+    calldata.extend_from_slice(&immutables(seed, 1_000, 100).abi_encode());
+    format!("0x{}", hex::encode(calldata))
+}
+
+#[test]
+fn decodes_the_secret_from_withdraw_calldata() {
+    assert_eq!(decode_withdraw_secret(&withdraw_calldata(1)), Some(secret(1)));
+    // Other functions and truncated calldata carry no secret
+    let mut other = function_selector("transfer(address,uint256)").to_vec();
+    other.extend_from_slice(&secret(1));
+    assert_eq!(decode_withdraw_secret(&hex::encode(other)), None);
+    assert_eq!(decode_withdraw_secret(&withdraw_calldata(1)[..60]), None);
+}
+
+#[test]
+fn secret_found_in_a_withdraw_transaction_is_recorded_as_confirmed() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::set_rpc(|_, request| {
+        assert_eq!(request["method"], "eth_getTransactionByHash");
+        let tx = serde_json::json!({ "to": EVM_ESCROW, "input": withdraw_calldata(1), "blockNumber": "0x3e8" });
+        RpcResult::Ok(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": tx }).to_string())
+    });
+
+    let outcome = mock::run(check_withdraw_transaction(escrow_id.clone(), format!("0x{}", hex::encode([9u8; 32]))));
+    assert!(matches!(outcome, Ok(MonitorOutcome::SecretFound(found)) if found == secret(1)));
+    let escrow = escrow(&escrow_id);
+    assert!(escrow.reveal_confirmed_at.is_some());
+    assert_eq!(escrow.reveal_block, Some(1_000));
+    assert_eq!(get_escrow_events(escrow_id).last().unwrap().kind, EscrowEventKind::SecretObserved { secret: secret(1) });
+}
+
+#[test]
+fn auto_withdrawal_failures_are_structured_errors() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::run(set_auto_withdraw(escrow_id.clone(), false)).unwrap();
+    assert_eq!(mock::run(auto_withdraw_on_evm_secret(escrow_id.clone())), Err(EscrowError::AutoWithdrawDisabled));
+
+    mock::run(set_auto_withdraw(escrow_id.clone(), true)).unwrap();
+    serve_evm_chain(1_000, vec![]);
+    assert_eq!(mock::run(auto_withdraw_on_evm_secret(escrow_id.clone())), Err(EscrowError::SecretNotRevealed));
+    assert!(matches!(
+        mock::run(auto_withdraw_on_evm_secret(escrow_id)),
+        Err(EscrowError::MonitorCooldown { .. })
+    ));
+    assert_eq!(mock::run(auto_withdraw_on_evm_secret("missing".to_string())), Err(EscrowError::EscrowNotFound));
+}
+
+#[test]
+fn last_rpc_response_is_kept_for_post_mortems() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    assert_eq!(get_last_rpc_response(escrow_id.clone()), None);
+
+    serve_evm_chain(1_000, vec![]);
+    mock::run(monitor_evm_secret_revelation(escrow_id.clone())).unwrap();
+    let (at, response) = get_last_rpc_response(escrow_id.clone()).unwrap();
+    assert_eq!(at, current_time_seconds());
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&response).unwrap()["result"], serde_json::json!([]));
+
+    // Errors are kept too, truncated on a character boundary
+    let error = "é".repeat(MAX_STORED_RPC_RESPONSE_BYTES);
+    let reply = error.clone();
+    mock::set_rpc(move |_, _| RpcResult::Err(reply.clone()));
+    mock::advance(60);
+    assert!(mock::run(monitor_evm_secret_revelation(escrow_id.clone())).is_err());
+    let (_, response) = get_last_rpc_response(escrow_id).unwrap();
+    assert_eq!(response.len(), MAX_STORED_RPC_RESPONSE_BYTES);
+    assert!(error.starts_with(&response));
+}
+
+#[test]
+fn sweep_aggregates_chains_and_survives_a_failing_one() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let on_chain = |seed: u8, chain_id: u64| insert_escrow(
+        immutables(seed, 1_000, 100), principal(1), None, None, chain_id, EVM_ESCROW.to_string(), None, None,
+    ).unwrap();
+    let revealed = on_chain(1, EVM_CHAIN);
+    let quiet = on_chain(2, 10);
+    let broken = [on_chain(3, 8453), on_chain(4, 8453)];
+    deposit(&revealed, payer).unwrap();
+    mock::advance(100);
+
+    let mut ethereum = evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    let mut optimism = evm_chain(1_000, vec![]);
+    mock::set_rpc(move |source, request| match source {
+        RpcSource::Chain(EVM_CHAIN) => ethereum(source, request),
+        RpcSource::Chain(10) => optimism(source, request),
+        _ => RpcResult::Err("upstream unavailable".to_string()),
+    });
+
+    let results = mock::run(auto_withdraw_sweep()).unwrap();
+    let unavailable = Err(EscrowError::Rpc(RpcError::Provider("upstream unavailable".to_string())));
+    // Grouped by chain id, each chain's escrows in creation order
+    assert_eq!(results, vec![
+        (revealed.clone(), Ok(())),
+        (quiet, Err(EscrowError::SecretNotRevealed)),
+        (broken[0].clone(), unavailable.clone()),
+        (broken[1].clone(), unavailable),
+    ]);
+    assert!(escrow(&revealed).withdrawn);
+}
@@ -0,0 +1,104 @@
+use super::*;
+
+/// Funded two-part partial-fill escrow of 1 000 (safety deposit 100), withdrawable
+fn two_part_escrow() -> String {
+    let (root, _) = two_part_tree();
+    let payer = mock::controller();
+    with_icp_ledger(payer);
+    let escrow_id = mock::run(create_partial_fill_escrow(
+        Immutables { hashlock: root, ..immutables(1, 1_000, 100) },
+        2, principal(1), None, EVM_CHAIN, EVM_ESCROW.to_string(),
+    )).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    mock::advance(100);
+    escrow_id
+}
+
+fn fill(escrow_id: &str, index: u32) -> Result<WithdrawalReceipt, EscrowError> {
+    let (_, leaves) = two_part_tree();
+    let proof = vec![leaves[2 - index as usize]];
+    mock::run(withdraw_partial(escrow_id.to_string(), secret(index as u8), proof, index))
+}
+
+#[test]
+fn cancelling_a_half_filled_escrow_refunds_the_remainder() {
+    let escrow_id = two_part_escrow();
+    fill(&escrow_id, 1).unwrap();
+    assert_eq!(escrow(&escrow_id).remaining_amount(), 500);
+
+    mock::advance(200);
+    let receipt = mock::run(cancel_escrow(escrow_id.clone())).unwrap();
+    assert_eq!(receipt.refunded_amount, 490u64);
+    assert_eq!(receipt.refund_account, Account::from(mock::controller()));
+    assert!(receipt.block_index.is_some());
+    assert_eq!(receipt.safety_deposit_returned, 90u64);
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 0);
+}
+
+#[test]
+fn fully_filled_escrow_cannot_be_cancelled() {
+    let escrow_id = two_part_escrow();
+    fill(&escrow_id, 1).unwrap();
+    fill(&escrow_id, 2).unwrap();
+
+    mock::advance(200);
+    assert_eq!(mock::run(cancel_escrow(escrow_id)).err(), Some(EscrowError::AlreadyWithdrawn));
+}
+
+fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    keccak256(&[a, b].concat())
+}
+
+/// Funded, withdrawable four-part escrow of 1 000; returns the escrow and each index's proof
+fn four_part_escrow() -> (String, impl Fn(u32) -> Vec<[u8; 32]>) {
+    let leaves: Vec<[u8; 32]> = (1..=4).map(|i| partial_fill_leaf(i, &secret(i as u8))).collect();
+    let pairs = [hash_pair(leaves[0], leaves[1]), hash_pair(leaves[2], leaves[3])];
+    let payer = mock::controller();
+    with_icp_ledger(payer);
+    let escrow_id = mock::run(create_partial_fill_escrow(
+        Immutables { hashlock: hash_pair(pairs[0], pairs[1]), ..immutables(1, 1_000, 100) },
+        4, principal(1), None, EVM_CHAIN, EVM_ESCROW.to_string(),
+    )).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    mock::advance(100);
+    let proof = move |index: u32| {
+        let i = index as usize - 1;
+        vec![leaves[i ^ 1], pairs[1 - i / 2]]
+    };
+    (escrow_id, proof)
+}
+
+#[test]
+fn fills_must_use_strictly_increasing_indices() {
+    let (escrow_id, proof) = four_part_escrow();
+    let fill = |index: u32| mock::run(withdraw_partial(escrow_id.clone(), secret(index as u8), proof(index), index));
+
+    fill(1).unwrap();
+    fill(3).unwrap();
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().filled_amount, 750);
+    // Repeated, out-of-order and out-of-range indices
+    assert_eq!(fill(3).err(), Some(EscrowError::InvalidInput));
+    assert_eq!(fill(2).err(), Some(EscrowError::InvalidInput));
+    assert_eq!(
+        mock::run(withdraw_partial(escrow_id.clone(), secret(5), proof(4), 5)).err(),
+        Some(EscrowError::InvalidInput),
+    );
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().last_filled_index, Some(3));
+
+    fill(4).unwrap();
+    assert!(escrow(&escrow_id).withdrawn);
+}
+
+#[test]
+fn fills_wait_for_a_confirmed_reveal_when_finality_is_enforced() {
+    let escrow_id = two_part_escrow();
+    set_enforce_finality_on_manual_withdraw(true).unwrap();
+
+    assert_eq!(fill(&escrow_id, 1).err(), Some(EscrowError::RevealNotConfirmed));
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().filled_amount, 0);
+
+    update_escrow(&escrow_id, |e| e.reveal_confirmed_at = Some(current_time_seconds()));
+    fill(&escrow_id, 1).unwrap();
+    assert_eq!(escrow(&escrow_id).partial_fill.unwrap().filled_amount, 500);
+}
@@ -0,0 +1,56 @@
+use super::*;
+
+const THROTTLED: &str = "HTTP 429 Too Many Requests (Retry-After: 20)";
+
+#[test]
+fn rate_limit_responses_are_classified_with_their_hint() {
+    assert_eq!(classify_rpc_error(THROTTLED), RpcError::RateLimited { retry_after: Some(20) });
+    assert_eq!(classify_rpc_error("Too many requests"), RpcError::RateLimited { retry_after: None });
+    assert_eq!(classify_rpc_error("execution reverted"), RpcError::Provider("execution reverted".to_string()));
+
+    let envelope = r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32005,"message":"limit exceeded","data":{"backoff_seconds":7}}}"#;
+    assert_eq!(extract_rpc_result(envelope), Err(RpcError::RateLimited { retry_after: Some(7) }));
+}
+
+#[test]
+fn numbers_merely_containing_rate_limit_codes_are_not_throttling() {
+    let not_found = "header not found for block 14290000";
+    assert_eq!(classify_rpc_error(not_found), RpcError::Provider(not_found.to_string()));
+    let reverted = r#"{"code":-32000,"message":"insufficient funds: have 4290 want 320050"}"#;
+    assert_eq!(classify_rpc_error(reverted), RpcError::Provider(reverted.to_string()));
+
+    assert_eq!(classify_rpc_error("status: 429, body: slow down"), RpcError::RateLimited { retry_after: None });
+    assert_eq!(classify_rpc_error(r#"{"code":429,"message":"busy"}"#), RpcError::RateLimited { retry_after: None });
+    assert_eq!(classify_rpc_error(r#"{"code": -32005, "message": "quota"}"#), RpcError::RateLimited { retry_after: None });
+}
+
+#[test]
+fn throttled_primary_rotates_to_an_alternate_provider() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    set_alternate_rpc_providers(EVM_CHAIN, vec!["https://alternate.example".to_string()]).unwrap();
+    let mut alternate = evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    mock::set_rpc(move |source, request| match source {
+        RpcSource::Chain(_) => RpcResult::Err(THROTTLED.to_string()),
+        RpcSource::Custom { .. } => alternate(source, request),
+    });
+
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id.clone()));
+    assert!(matches!(outcome, Ok(MonitorOutcome::SecretFound(found)) if found == secret(1)));
+    assert_eq!(escrow(&escrow_id).rpc_backoff_until, 0);
+}
+
+#[test]
+fn backs_off_for_the_hinted_time_when_every_provider_is_throttled() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::set_rpc(|_, _| RpcResult::Err(THROTTLED.to_string()));
+
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id.clone()));
+    assert_eq!(outcome.err(), Some(EscrowError::Rpc(RpcError::RateLimited { retry_after: Some(20) })));
+    assert_eq!(escrow(&escrow_id).rpc_backoff_until, mock::GENESIS_SECS + 20);
+
+    // Within the backoff the provider isn't called again
+    let calls = mock::rpc_calls();
+    mock::advance(19);
+    assert!(mock::run(monitor_evm_secret_revelation(escrow_id.clone())).is_err());
+    assert_eq!(mock::rpc_calls(), calls);
+}
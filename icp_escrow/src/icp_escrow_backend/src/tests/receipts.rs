@@ -0,0 +1,114 @@
+use super::*;
+
+/// Funded flat escrow for order 1, deposited by the controller (its creator)
+fn funded_escrow() -> String {
+    let depositor = mock::controller();
+    with_icp_ledger(depositor);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, depositor).unwrap();
+    escrow_id
+}
+
+#[test]
+fn withdrawal_receipt_matches_the_transfer() {
+    let escrow_id = funded_escrow();
+    mock::advance(100);
+
+    let receipt = mock::run(withdraw_with_secret(escrow_id.clone(), secret(1))).unwrap();
+    assert_eq!(receipt.amount_transferred, 990u64);
+    assert_eq!(receipt.fee, 10u64);
+    assert_eq!(receipt.recipient, Account::from(principal(1)));
+    assert_eq!(mock::balance(icp_ledger(), receipt.recipient), 990);
+    assert_eq!(receipt.safety_deposit_to, Account::from(mock::controller()));
+    assert!(receipt.block_index.is_some());
+    assert_eq!(receipt.auction_surplus_returned, 0u64);
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 0);
+}
+
+#[test]
+fn refund_receipt_matches_the_refund() {
+    let escrow_id = funded_escrow();
+    let refund_account = Account::from(mock::controller());
+    let before = mock::balance(icp_ledger(), refund_account);
+    mock::advance(300);
+
+    let receipt = mock::run(cancel_escrow(escrow_id.clone())).unwrap();
+    assert_eq!(receipt.refunded_amount, 990u64);
+    assert_eq!(receipt.safety_deposit_returned, 90u64);
+    assert_eq!(receipt.refund_account, refund_account);
+    assert_eq!(receipt.safety_deposit_to, refund_account);
+    assert!(receipt.block_index.is_some());
+    assert!(matches!(receipt.kind, CancellationKind::RefundedToMaker(_)));
+    assert_eq!(mock::balance(icp_ledger(), refund_account), before + 990 + 90);
+    assert_eq!(mock::balance(icp_ledger(), deposit_account(&escrow_id)), 0);
+}
+
+#[test]
+fn cancelling_an_unfunded_escrow_records_that_nothing_was_refunded() {
+    with_icp_ledger(principal(7));
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    mock::advance(300);
+
+    let receipt = mock::run(cancel_escrow(escrow_id.clone())).unwrap();
+    assert!(matches!(receipt.kind, CancellationKind::NoFundsToRefund));
+    assert_eq!(receipt.refunded_amount, 0u64);
+    assert_eq!(receipt.block_index, None);
+    assert!(matches!(escrow(&escrow_id).cancellation, Some(CancellationKind::NoFundsToRefund)));
+}
+
+#[test]
+fn cancelling_a_funded_escrow_records_the_refund() {
+    let escrow_id = funded_escrow();
+    mock::advance(300);
+
+    mock::run(cancel_escrow(escrow_id.clone())).unwrap();
+    assert!(matches!(
+        escrow(&escrow_id).cancellation,
+        Some(CancellationKind::RefundedToMaker(refunded)) if refunded == 990u64
+    ));
+}
+
+/// Public withdrawal of a funded escrow with a 1 000 safety deposit, executed by `executor`
+fn public_withdrawal(executor: Principal, reward_bps: Option<u16>) -> WithdrawalReceipt {
+    let depositor = mock::controller();
+    with_icp_ledger(depositor);
+    let escrow_id = create(immutables(1, 1_000, 1_000)).unwrap();
+    deposit(&escrow_id, depositor).unwrap();
+    if let Some(bps) = reward_bps {
+        set_executor_reward_bps(escrow_id.clone(), bps).unwrap();
+    }
+    mock::advance(200);
+    mock::set_caller(executor);
+    let receipt = mock::run(public_withdraw_with_secret(escrow_id, secret(1))).unwrap();
+    mock::set_caller(mock::controller());
+    receipt
+}
+
+#[test]
+fn public_withdrawal_pays_the_whole_safety_deposit_to_the_executor_by_default() {
+    let executor = principal(50);
+    let receipt = public_withdrawal(executor, None);
+
+    assert_eq!(receipt.safety_deposit_to, Account::from(executor));
+    assert_eq!(receipt.safety_deposit_paid, 990u64);
+    assert_eq!(receipt.safety_deposit_rebate, 0u64);
+    assert_eq!(mock::balance(icp_ledger(), Account::from(executor)), 990);
+}
+
+#[test]
+fn public_withdrawal_splits_the_safety_deposit_per_the_configured_bps() {
+    let executor = principal(50);
+    let taker_before = 1_000_000_000 - 2_000 - 10;
+    let receipt = public_withdrawal(executor, Some(2_500));
+
+    assert_eq!(receipt.safety_deposit_paid, 240u64);
+    assert_eq!(receipt.safety_deposit_rebate, 740u64);
+    assert_eq!(mock::balance(icp_ledger(), Account::from(executor)), 240);
+    assert_eq!(mock::balance(icp_ledger(), Account::from(mock::controller())), taker_before + 740);
+}
+
+#[test]
+fn reward_share_is_capped_at_100_percent() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    assert_eq!(set_executor_reward_bps(escrow_id, 10_001), Err(EscrowError::InvalidInput));
+}
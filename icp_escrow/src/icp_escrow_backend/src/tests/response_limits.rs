@@ -0,0 +1,65 @@
+use super::*;
+
+const TOO_LARGE: &str = "Http request size limit exceeded";
+
+#[test]
+fn logs_response_budget_scales_with_blocks_and_reveal_source() {
+    assert_eq!(estimate_logs_response_bytes(0), RPC_ENVELOPE_BYTES);
+    assert_eq!(estimate_logs_response_bytes(3), RPC_ENVELOPE_BYTES + 3 * LOG_ENTRY_BYTES);
+    assert_eq!(estimate_logs_response_bytes(u64::MAX), MAX_HTTP_RESPONSE_BYTES);
+
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    assert_eq!(logs_response_limit(&escrow(&escrow_id), 10), RPC_ENVELOPE_BYTES + 10 * LOG_ENTRY_BYTES);
+    set_reveal_source(escrow_id.clone(), RevealSource::Calldata).unwrap();
+    assert_eq!(
+        logs_response_limit(&escrow(&escrow_id), 10),
+        RPC_ENVELOPE_BYTES + 10 * CONTRACT_LOGS_PER_BLOCK * LOG_ENTRY_BYTES,
+    );
+}
+
+#[test]
+fn admin_limit_overrides_the_estimate_within_the_outcall_cap() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    assert_eq!(set_logs_max_response_bytes(Some(0)), Err(EscrowError::InvalidInput));
+    assert_eq!(set_logs_max_response_bytes(Some(MAX_HTTP_RESPONSE_BYTES + 1)), Err(EscrowError::InvalidInput));
+
+    set_logs_max_response_bytes(Some(50_000)).unwrap();
+    assert_eq!(logs_response_limit(&escrow(&escrow_id), 10_000), 50_000);
+    set_logs_max_response_bytes(None).unwrap();
+    assert_eq!(logs_response_limit(&escrow(&escrow_id), 10), RPC_ENVELOPE_BYTES + 10 * LOG_ENTRY_BYTES);
+}
+
+#[test]
+fn too_small_budget_is_doubled_until_the_logs_fit() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    let mut chain = evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    let mut rejected = 0;
+    mock::set_rpc(move |source, request| {
+        if request["method"] == "eth_getLogs" && rejected < 2 {
+            rejected += 1;
+            return RpcResult::Err(TOO_LARGE.to_string());
+        }
+        chain(source, request)
+    });
+
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id));
+    assert!(matches!(outcome, Ok(MonitorOutcome::SecretFound(found)) if found == secret(1)));
+    let limits = mock::rpc_response_limits("eth_getLogs");
+    assert_eq!(limits.len(), 3);
+    assert_eq!(limits[1], limits[0] * 2);
+    assert_eq!(limits[2], limits[1] * 2);
+}
+
+#[test]
+fn response_too_large_at_the_outcall_cap_is_reported() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    let mut chain = evm_chain(1_000, vec![]);
+    mock::set_rpc(move |source, request| match request["method"].as_str() {
+        Some("eth_getLogs") => RpcResult::Err(TOO_LARGE.to_string()),
+        _ => chain(source, request),
+    });
+
+    let outcome = mock::run(monitor_evm_secret_revelation(escrow_id));
+    assert_eq!(outcome.err(), Some(EscrowError::Rpc(RpcError::ResponseTooLarge { limit: MAX_HTTP_RESPONSE_BYTES })));
+    assert_eq!(mock::rpc_response_limits("eth_getLogs").last(), Some(&MAX_HTTP_RESPONSE_BYTES));
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+fn reveal_order(escrow_id: &str) -> Option<bool> {
+    was_revealed_on_icp_first(escrow_id.to_string()).unwrap()
+}
+
+#[test]
+fn secret_submitted_before_the_evm_reveal_is_icp_first() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    assert_eq!(reveal_order(&escrow_id), None);
+
+    submit_secret(escrow_id.clone(), secret(1)).unwrap();
+    // Not yet seen on EVM: it may have been revealed there unobserved
+    assert_eq!(reveal_order(&escrow_id), None);
+
+    mock::advance(30);
+    serve_evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    mock::run(monitor_evm_secret_revelation(escrow_id.clone())).unwrap();
+    assert_eq!(reveal_order(&escrow_id), Some(true));
+}
+
+#[test]
+fn reveal_detected_on_evm_before_submission_is_evm_first() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    serve_evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    mock::run(monitor_evm_secret_revelation(escrow_id.clone())).unwrap();
+    assert_eq!(reveal_order(&escrow_id), Some(false));
+
+    mock::advance(30);
+    submit_secret(escrow_id.clone(), secret(1)).unwrap();
+    assert_eq!(reveal_order(&escrow_id), Some(false));
+    assert_eq!(was_revealed_on_icp_first("missing".to_string()), Err(EscrowError::EscrowNotFound));
+}
+
+#[test]
+fn revealed_secrets_list_skips_unrevealed_escrows_and_masks_cleared_secrets() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let submitted = create(immutables(1, 1_000, 100)).unwrap();
+    create(immutables(2, 1_000, 100)).unwrap();
+    let cleared = create(immutables(3, 1_000, 100)).unwrap();
+    let withdrawn = create(immutables(4, 1_000, 100)).unwrap();
+    deposit(&withdrawn, payer).unwrap();
+
+    submit_secret(submitted.clone(), secret(1)).unwrap();
+    let submitted_at = current_time_seconds();
+    submit_secret(cleared.clone(), secret(3)).unwrap();
+    update_escrow(&cleared, |escrow| escrow.secret = None);
+    mock::advance(100);
+    mock::run(withdraw_with_secret(withdrawn.clone(), secret(4))).unwrap();
+
+    assert_eq!(list_revealed_secrets(0, 10), vec![
+        (submitted.clone(), Some(secret(1)), submitted_at),
+        (cleared.clone(), None, submitted_at),
+        (withdrawn.clone(), Some(secret(4)), submitted_at + 100),
+    ]);
+    let page: Vec<String> = list_revealed_secrets(1, 1).into_iter().map(|(id, ..)| id).collect();
+    assert_eq!(page, vec![cleared]);
+    assert!(list_revealed_secrets(3, 10).is_empty());
+}
@@ -0,0 +1,22 @@
+use super::*;
+
+#[test]
+fn self_test_reports_all_green() {
+    let report = run_self_test();
+    let failed: Vec<_> = report.checks.iter()
+        .filter(|check| !check.passed)
+        .map(|check| (check.name.clone(), check.detail.clone()))
+        .collect();
+    assert!(failed.is_empty(), "{:?}", failed);
+    assert!(report.passed);
+    assert_eq!(report.checks.len(), 6);
+    assert!(report.checks.iter().all(|check| check.detail.is_none()));
+}
+
+#[test]
+fn self_test_check_carries_the_failure_detail() {
+    let check = self_test_check("broken", Err("expected 1, got 2".to_string()));
+    assert!(!check.passed);
+    assert_eq!(check.name, "broken");
+    assert_eq!(check.detail.as_deref(), Some("expected 1, got 2"));
+}
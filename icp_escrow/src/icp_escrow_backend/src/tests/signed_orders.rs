@@ -0,0 +1,171 @@
+use k256::ecdsa::SigningKey;
+use num_bigint::BigUint;
+
+use super::*;
+
+fn maker_key() -> SigningKey {
+    SigningKey::from_slice(&[0x42; 32]).unwrap()
+}
+
+fn address_word(key: &SigningKey) -> Address {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(&evm_address_of(key.verifying_key()));
+    word
+}
+
+/// 65-byte `r || s || v` EIP-712 signature by `key`
+fn sign(key: &SigningKey, chain_id: u64, struct_hash: &[u8; 32]) -> Vec<u8> {
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&eip712_digest(chain_id, struct_hash)).unwrap();
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+    bytes
+}
+
+fn signed_order(seed: u8, nonce: u64, recipient: Principal) -> (Immutables, Vec<u8>) {
+    let key = maker_key();
+    let immutables = Immutables { maker: address_word(&key), ..immutables(seed, 1_000, 100) };
+    let signature = sign(&key, EVM_CHAIN, &order_authorization_hash(&immutables, recipient, None, nonce));
+    (immutables, signature)
+}
+
+fn create_signed(immutables: Immutables, nonce: u64, signature: Vec<u8>, recipient: Principal) -> Result<String, EscrowError> {
+    mock::run(create_escrow_with_signed_order(
+        immutables,
+        nonce,
+        signature,
+        recipient,
+        None,
+        EVM_CHAIN,
+        EVM_ESCROW.to_string(),
+    ))
+}
+
+fn raise_nonce(key: &SigningKey) -> Result<u64, EscrowError> {
+    let maker = address_word(key);
+    let signature = sign(key, EVM_CHAIN, &nonce_increment_hash(&maker, current_maker_nonce(&maker)));
+    increment_nonce(format!("0x{}", hex::encode(&maker[12..])), EVM_CHAIN, signature)
+}
+
+#[test]
+fn sec1_keys_map_to_their_ethereum_address() {
+    // Private key 1: the generator point, compressed and uncompressed
+    let generator = hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+    let expected = hex::decode("7e5f4552091a69125d5dfcb7b8c2659029395bdf").unwrap();
+    assert_eq!(evm_address_from_sec1(&generator).unwrap().to_vec(), expected);
+
+    let key = SigningKey::from_slice(&u64_to_u256(1)).unwrap();
+    let uncompressed = key.verifying_key().to_encoded_point(false);
+    assert_eq!(evm_address_from_sec1(uncompressed.as_bytes()).unwrap().to_vec(), expected);
+    assert!(evm_address_from_sec1(&generator[1..]).is_err());
+}
+
+#[test]
+fn ecrecover_returns_the_signer_and_rejects_malleable_signatures() {
+    let key = maker_key();
+    let digest = keccak256(b"message");
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&digest).unwrap();
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+    assert_eq!(ecrecover(&digest, &bytes).unwrap(), evm_address_of(key.verifying_key()));
+
+    // Same signature with s' = n - s and the parity flipped recovers the same key on the EVM,
+    // but OpenZeppelin (and we) only accept the low-s form
+    let n = BigUint::parse_bytes(b"fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141", 16).unwrap();
+    let high_s = (n - BigUint::from_bytes_be(&bytes[32..64])).to_bytes_be();
+    let mut malleable = bytes.clone();
+    malleable[32..64].copy_from_slice(&high_s);
+    malleable[64] ^= 1;
+    assert!(ecrecover(&digest, &malleable).is_err());
+
+    bytes[64] = 29;
+    assert!(ecrecover(&digest, &bytes).is_err());
+    assert!(ecrecover(&digest, &bytes[..64]).is_err());
+}
+
+#[test]
+fn signed_order_creates_one_escrow_only() {
+    let recipient = principal(1);
+    let (immutables, signature) = signed_order(1, 0, recipient);
+
+    let escrow_id = create_signed(immutables.clone(), 0, signature.clone(), recipient).unwrap();
+    assert_eq!(escrow(&escrow_id).icp_recipient, recipient);
+    assert_eq!(create_signed(immutables, 0, signature, recipient), Err(EscrowError::DuplicateOrder));
+}
+
+#[test]
+fn signature_does_not_authorize_other_recipients_amounts_or_chains() {
+    let recipient = principal(1);
+    let (immutables, signature) = signed_order(1, 0, recipient);
+
+    let attacker = principal(66);
+    assert_eq!(create_signed(immutables.clone(), 0, signature.clone(), attacker), Err(EscrowError::InvalidSignature));
+
+    let inflated = Immutables { amount: u64_to_u256(1_000_000), ..immutables.clone() };
+    assert_eq!(create_signed(inflated, 0, signature.clone(), recipient), Err(EscrowError::InvalidSignature));
+
+    let other_chain = mock::run(create_escrow_with_signed_order(
+        immutables, 0, signature, recipient, None, 137, EVM_ESCROW.to_string(),
+    ));
+    assert_eq!(other_chain, Err(EscrowError::InvalidSignature));
+}
+
+#[test]
+fn raising_the_nonce_invalidates_lower_nonce_orders() {
+    let recipient = principal(1);
+    let (stale, stale_signature) = signed_order(1, 0, recipient);
+
+    assert_eq!(raise_nonce(&maker_key()), Ok(1));
+
+    assert_eq!(create_signed(stale, 0, stale_signature, recipient), Err(EscrowError::StaleNonce));
+    let (current, signature) = signed_order(2, 1, recipient);
+    assert!(create_signed(current, 1, signature, recipient).is_ok());
+}
+
+#[test]
+fn only_the_maker_can_raise_their_nonce() {
+    let maker = address_word(&maker_key());
+    let impostor = SigningKey::from_slice(&[0x07; 32]).unwrap();
+    let signature = sign(&impostor, EVM_CHAIN, &nonce_increment_hash(&maker, 0));
+
+    let result = increment_nonce(format!("0x{}", hex::encode(&maker[12..])), EVM_CHAIN, signature);
+    assert_eq!(result, Err(EscrowError::InvalidSignature));
+    assert_eq!(current_maker_nonce(&maker), 0);
+}
+
+fn import(seed: u8, signature: Option<Vec<u8>>) -> SignedOrder {
+    let recipient = principal(1);
+    let (immutables, valid) = signed_order(seed, 0, recipient);
+    SignedOrder {
+        immutables,
+        nonce: 0,
+        signature: signature.unwrap_or(valid),
+        icp_recipient: recipient,
+        token_ledger: None,
+        evm_chain_id: EVM_CHAIN,
+        evm_escrow_address: EVM_ESCROW.to_string(),
+    }
+}
+
+#[test]
+fn import_reports_each_order_valid_forged_or_duplicate() {
+    let forged = sign(&SigningKey::from_slice(&[0x07; 32]).unwrap(), EVM_CHAIN, &[0u8; 32]);
+    let results = import_signed_orders(vec![import(1, None), import(2, Some(forged)), import(1, None)]);
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].0, format!("0x{}", hex::encode([1u8; 32])));
+    let escrow_id = results[0].1.clone().unwrap();
+    assert_eq!(escrow(&escrow_id).immutables.order_hash, [1; 32]);
+    assert_eq!(results[1].1, Err(EscrowError::InvalidSignature));
+    assert_eq!(results[2].1, Err(EscrowError::DuplicateOrder));
+    assert_eq!(ESCROWS.with(|escrows| escrows.borrow().len()), 1);
+}
+
+#[test]
+fn import_rejects_orders_past_the_batch_cap() {
+    let orders: Vec<SignedOrder> = (1..=MAX_PAGE_SIZE as u8 + 2).map(|seed| import(seed, None)).collect();
+    let results = import_signed_orders(orders);
+
+    assert_eq!(results.len(), MAX_PAGE_SIZE as usize + 2);
+    assert!(results[..MAX_PAGE_SIZE as usize].iter().all(|(_, result)| result.is_ok()));
+    assert!(results[MAX_PAGE_SIZE as usize..].iter().all(|(_, result)| *result == Err(EscrowError::InvalidInput)));
+}
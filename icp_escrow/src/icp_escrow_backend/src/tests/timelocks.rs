@@ -0,0 +1,53 @@
+use super::*;
+
+#[test]
+fn timelock_batch_answers_in_input_order_with_unknown_ids_failing() {
+    let first = create(immutables(1, 1_000, 100)).unwrap();
+    let second = create(immutables(2, 1_000, 100)).unwrap();
+    mock::advance(150);
+
+    let results = get_timelock_info_batch(vec![second.clone(), "missing".to_string(), first.clone()]);
+
+    let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, vec![second.as_str(), "missing", first.as_str()]);
+    assert_eq!(results[1].1.as_ref().err(), Some(&EscrowError::EscrowNotFound));
+    let info = results[0].1.as_ref().unwrap();
+    let withdrawal = info.iter().find(|i| matches!(i.stage, TimelockStage::DstWithdrawal)).unwrap();
+    assert_eq!(withdrawal.timestamp, mock::GENESIS_SECS + 100);
+    assert!(withdrawal.is_met);
+    let cancellation = info.iter().find(|i| matches!(i.stage, TimelockStage::DstCancellation)).unwrap();
+    assert!(!cancellation.is_met);
+}
+
+#[test]
+fn timelock_batch_rejects_ids_past_the_cap() {
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    let ids = vec![escrow_id; MAX_PAGE_SIZE as usize + 1];
+
+    let results = get_timelock_info_batch(ids);
+    assert_eq!(results.len(), MAX_PAGE_SIZE as usize + 1);
+    assert!(results[..MAX_PAGE_SIZE as usize].iter().all(|(_, info)| info.is_ok()));
+    assert_eq!(results[MAX_PAGE_SIZE as usize].1.as_ref().err(), Some(&EscrowError::InvalidInput));
+}
+
+#[test]
+fn funding_refresh_batch_rejects_oversized_batches() {
+    let ids = vec!["missing".to_string(); MAX_PAGE_SIZE as usize + 1];
+    assert_eq!(mock::run(refresh_funding_batch(ids)), Err(EscrowError::InvalidInput));
+}
+
+#[test]
+fn funding_refresh_batch_picks_up_deposits_until_cycles_run_low() {
+    with_icp_ledger(principal(7));
+    let funded = create(immutables(1, 1_000, 100)).unwrap();
+    let unfunded = create(immutables(2, 1_000, 100)).unwrap();
+    mock::mint(icp_ledger(), deposit_account(&funded), 1_100);
+
+    let results = mock::run(refresh_funding_batch(vec![funded.clone(), unfunded.clone()])).unwrap();
+    assert_eq!(results, vec![(funded.clone(), Ok(true)), (unfunded.clone(), Ok(false))]);
+    assert!(escrow(&funded).funded);
+
+    mock::set_cycles(FUNDING_REFRESH_CYCLES_RESERVE);
+    let results = mock::run(refresh_funding_batch(vec![unfunded.clone()])).unwrap();
+    assert_eq!(results, vec![(unfunded, Err(EscrowError::CyclesBudgetExhausted))]);
+}
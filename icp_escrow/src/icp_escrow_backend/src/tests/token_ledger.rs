@@ -0,0 +1,41 @@
+use super::*;
+
+/// Escrow of 1 000 units of a token mapped to `ledger`, with its 100 safety deposit in the
+/// same token
+fn token_escrow(ledger: Principal) -> String {
+    let token = register_token(ledger);
+    insert_escrow(
+        Immutables { token, ..immutables(1, 1_000, 100) },
+        principal(1), Some(ledger), Some(ledger), EVM_CHAIN, EVM_ESCROW.to_string(), None, None,
+    ).unwrap()
+}
+
+#[test]
+fn unfunded_escrow_moves_to_a_ledger_only_if_its_safety_deposit_meets_the_floor() {
+    let (old_ledger, new_ledger) = (principal(40), principal(41));
+    let escrow_id = token_escrow(old_ledger);
+    register_token(new_ledger);
+    set_min_safety_deposit(Some(new_ledger), candid::Nat::from(500u64)).unwrap();
+
+    assert_eq!(update_token_ledger(escrow_id.clone(), Some(new_ledger)), Err(EscrowError::InvalidInput));
+    assert_eq!(escrow(&escrow_id).token_ledger, Some(old_ledger));
+    assert_eq!(escrow(&escrow_id).safety_deposit_ledger, Some(old_ledger));
+
+    set_min_safety_deposit(Some(new_ledger), candid::Nat::from(100u64)).unwrap();
+    update_token_ledger(escrow_id.clone(), Some(new_ledger)).unwrap();
+    assert_eq!(escrow(&escrow_id).token_ledger, Some(new_ledger));
+    assert_eq!(escrow(&escrow_id).safety_deposit_ledger, Some(new_ledger));
+}
+
+#[test]
+fn funded_escrow_keeps_its_ledger() {
+    let (old_ledger, new_ledger) = (principal(40), principal(41));
+    let payer = principal(7);
+    let escrow_id = token_escrow(old_ledger);
+    mock::mint(old_ledger, Account::from(payer), 10_000);
+    deposit(&escrow_id, payer).unwrap();
+    register_token(new_ledger);
+
+    assert_eq!(update_token_ledger(escrow_id.clone(), Some(new_ledger)), Err(EscrowError::InvalidInput));
+    assert_eq!(escrow(&escrow_id).token_ledger, Some(old_ledger));
+}
@@ -0,0 +1,46 @@
+use super::*;
+
+fn failed_transfers() -> usize {
+    get_audit_log_by_event(AuditEvent::TransferFailed, 0, MAX_PAGE_SIZE).len()
+}
+
+#[test]
+fn max_withdrawal_failures_must_be_positive() {
+    assert_eq!(set_max_withdrawal_failures(0), Err(EscrowError::InvalidInput));
+    set_max_withdrawal_failures(2).unwrap();
+    assert_eq!(CONFIG.with(|config| config.borrow().max_withdrawal_failures), Some(2));
+}
+
+#[test]
+fn escrow_needs_attention_after_the_threshold_and_is_no_longer_paid_out_automatically() {
+    let payer = principal(7);
+    with_icp_ledger(payer);
+    let escrow_id = create(immutables(1, 1_000, 100)).unwrap();
+    deposit(&escrow_id, payer).unwrap();
+    start_auto_monitor(escrow_id.clone(), 60).unwrap();
+    set_max_withdrawal_failures(2).unwrap();
+    mock::fail_ledger_method(icp_ledger(), Some("icrc1_transfer"));
+    mock::advance(100);
+
+    let withdraw = || mock::run(withdraw_with_secret(escrow_id.clone(), secret(1)));
+    assert_eq!(withdraw().err(), Some(EscrowError::TokenTransferFailed));
+    assert!(!escrow(&escrow_id).needs_attention);
+    assert_eq!(withdraw().err(), Some(EscrowError::TokenTransferFailed));
+
+    let flagged = escrow(&escrow_id);
+    assert_eq!(flagged.withdrawal_failure_count, 2);
+    assert!(flagged.needs_attention);
+    assert!(!flagged.auto_withdraw_enabled);
+    assert!(!flagged.withdrawn);
+    assert_eq!(health_check().needs_attention, vec![escrow_id.clone()]);
+    assert_eq!(mock::pending_timers(), 0);
+
+    // Automation leaves it alone from now on, even with the secret revealed on EVM
+    let attempts = failed_transfers();
+    serve_evm_chain(1_000, vec![reveal_log(1, 1_000)]);
+    mock::advance(600);
+    assert_eq!(mock::run(auto_withdraw_on_evm_secret(escrow_id.clone())), Err(EscrowError::AutoWithdrawDisabled));
+    assert!(mock::run(auto_withdraw_sweep()).unwrap().is_empty());
+    assert_eq!(failed_transfers(), attempts);
+    assert_eq!(escrow(&escrow_id).withdrawal_failure_count, 2);
+}